@@ -0,0 +1,83 @@
+use actix_web::{body::MessageBody, dev::{ServiceRequest, ServiceResponse}, error::{ErrorForbidden, ErrorInternalServerError}, http::header::AUTHORIZATION, middleware::Next, web, Error};
+
+use crate::jwt::JwtValidator;
+
+/// Only lets through requests bearing a valid access token with the `admin` role.
+/// Meant for [`actix_web::middleware::from_fn`], with a [`JwtValidator`] in app data.
+/// Unlike regular requests, admin requests are refused while revocations can't be checked.
+pub async fn require_admin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(validator) = req.app_data::<web::Data<JwtValidator>>() else {
+        tracing::error!("JwtValidator not found in app data");
+        return Err(ErrorInternalServerError(""));
+    };
+
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    let is_admin = match token {
+        Some(token) => match validator.validate_token(token).await {
+            Ok(claims) if claims.is_admin() => match validator.is_revoked(&claims.jti).await {
+                Ok(revoked) => !revoked,
+                Err(e) => {
+                    tracing::error!("Failed to check token revocation: {:?}", e);
+                    false
+                },
+            },
+            Ok(_) => false,
+            Err(e) => {
+                tracing::warn!("JWT validation failed: {}", e);
+                false
+            },
+        },
+        None => false,
+    };
+
+    if !is_admin {
+        return Err(ErrorForbidden("Admin access required"));
+    }
+
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::{header::AUTHORIZATION, StatusCode}, middleware::from_fn, test, web, App, HttpResponse};
+
+    use super::require_admin;
+    use crate::jwt::JwtValidator;
+
+    async fn status(req: test::TestRequest) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                // Nothing listens there, tokens that need keys fail to validate
+                .app_data(web::Data::new(JwtValidator::new("http://127.0.0.1:9".to_string())))
+                .wrap(from_fn(require_admin))
+                .route("/", web::get().to(HttpResponse::Ok))
+        ).await;
+
+        match test::try_call_service(&app, req.to_request()).await {
+            Ok(res) => res.status(),
+            Err(e) => e.as_response_error().status_code(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn rejects_requests_without_token() {
+        assert_eq!(status(test::TestRequest::get()).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn rejects_malformed_tokens() {
+        for value in ["Basic YWRtaW46YWRtaW4=", "Bearer ", "Bearer not-a-jwt"] {
+            let req = test::TestRequest::get().insert_header((AUTHORIZATION, value));
+
+            assert_eq!(status(req).await, StatusCode::FORBIDDEN, "{}", value);
+        }
+    }
+}
\ No newline at end of file
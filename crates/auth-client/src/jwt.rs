@@ -4,6 +4,12 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+/// How long a revocation may take to be enforced by a service instance
+const REVOCATION_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Authenticates services to auth-service's internal endpoints
+const INTERNAL_TOKEN_HEADER: &str = "x-internal-token";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
@@ -15,6 +21,18 @@ pub struct Claims {
     pub roles: Vec<String>
 }
 
+#[cfg(feature = "cache")]
+impl cache::actix::CacheSubject for Claims {
+    fn cache_subject(&self) -> &str {
+        &self.sub
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RevocationStatus {
+    revoked: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct JwksResponse {
     pub keys: Vec<Jwk>,
@@ -35,7 +53,9 @@ pub struct Jwk {
 pub struct JwtValidator {
     client: Client,
     auth_service_url: String,
+    internal_token: Option<String>,
     keys_cache: Cache<String, DecodingKey>,
+    revocations: Cache<String, bool>,
     validation: Validation,
 }
 
@@ -51,14 +71,27 @@ impl JwtValidator {
             .time_to_idle(Duration::from_secs(1800))
             .build();
 
+        let revocations = Cache::builder()
+            .max_capacity(100_000)
+            .time_to_live(REVOCATION_CACHE_TTL)
+            .build();
+
         Self {
             client: Client::new(),
             auth_service_url,
+            internal_token: None,
             keys_cache,
+            revocations,
             validation,
         }
     }
 
+    /// Token shared with auth-service, without it revocations can't be checked.
+    pub fn internal_token(mut self, token: Option<String>) -> Self {
+        self.internal_token = token;
+        self
+    }
+
     pub async fn validate_token(&self, token: &str) -> Result<Claims, JwtError> {
         let header = decode_header(token)
             .map_err(|e| JwtError::InvalidToken(format!("Invalid header: {}", e)))?;
@@ -136,6 +169,44 @@ impl JwtValidator {
         Ok(())
     }
 
+    /// Whether auth-service has revoked the token with this `jti`.
+    /// Answers are cached for `REVOCATION_CACHE_TTL`, failed lookups are not.
+    pub async fn is_revoked(&self, jti: &str) -> Result<bool, JwtError> {
+        self.revocations
+            .try_get_with_by_ref(jti, self.fetch_revocation(jti))
+            .await
+            .map_err(|e| JwtError::NetworkError(e.to_string()))
+    }
+
+    async fn fetch_revocation(&self, jti: &str) -> Result<bool, JwtError> {
+        let url = format!("{}/oauth/revoked/{}", self.auth_service_url, jti);
+
+        let mut request = self.client
+            .get(&url)
+            .timeout(Duration::from_secs(2));
+
+        if let Some(token) = &self.internal_token {
+            request = request.header(INTERNAL_TOKEN_HEADER, token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| JwtError::NetworkError(format!("Failed to check revocation: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(JwtError::NetworkError(format!(
+                "Auth service returned status: {}", 
+                response.status()
+            )));
+        }
+
+        let status: RevocationStatus = response.json().await
+            .map_err(|e| JwtError::ParseError(format!("Failed to parse revocation status: {}", e)))?;
+
+        Ok(status.revoked)
+    }
+
     fn jwk_to_decoding_key(&self, jwk: &Jwk) -> Result<Option<DecodingKey>, JwtError> {
         match jwk.kty.as_str() {
             "RSA" => {
@@ -198,6 +269,10 @@ impl Claims {
         roles.iter().any(|role| self.has_role(role))
     }
 
+    pub fn has_all_roles(&self, roles: &[&str]) -> bool {
+        roles.iter().all(|role| self.has_role(role))
+    }
+
     pub fn is_admin(&self) -> bool {
         self.has_role("admin")
     }
@@ -210,6 +285,16 @@ impl Claims {
         self.scope.split_whitespace().collect()
     }
 
+    pub fn has_any_scope(&self, scopes: &[&str]) -> bool {
+        let granted = self.get_scopes();
+        scopes.iter().any(|scope| granted.contains(scope))
+    }
+
+    pub fn has_all_scopes(&self, scopes: &[&str]) -> bool {
+        let granted = self.get_scopes();
+        scopes.iter().all(|scope| granted.contains(scope))
+    }
+
     pub fn is_valid(&self) -> bool {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
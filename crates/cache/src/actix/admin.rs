@@ -0,0 +1,102 @@
+use actix_web::{web, HttpResponse, Responder, Scope};
+use serde::Serialize;
+
+use crate::{cache::HybridCache, serializer::CacheSerializer};
+
+#[derive(Serialize)]
+struct PurgeResponse {
+    deleted: usize,
+}
+
+/// Stats, key lookup and purging for a `web::Data<HybridCache<String, V, S>>`
+/// registered in the app. Callers are responsible for protecting the scope.
+pub fn scope<V, S>(path: &str) -> Scope
+where
+    V: Serialize + Clone + Send + Sync + 'static,
+    S: CacheSerializer<V> + Send + Sync + 'static,
+{
+    web::scope(path)
+        .route("/stats", web::get().to(get_stats::<V, S>))
+        .route("/keys/{key:.*}", web::get().to(get_key::<V, S>))
+        .route("/keys/{key:.*}", web::delete().to(purge_key::<V, S>))
+        .route("/prefixes/{prefix:.*}", web::delete().to(purge_prefix::<V, S>))
+        .route("", web::delete().to(purge_all::<V, S>))
+}
+
+async fn get_stats<V, S>(
+    cache: web::Data<HybridCache<String, V, S>>
+) -> impl Responder
+where
+    V: Serialize + Clone + Send + Sync + 'static,
+    S: CacheSerializer<V> + Send + Sync + 'static,
+{
+    HttpResponse::Ok().json(cache.stats().await)
+}
+
+async fn get_key<V, S>(
+    cache: web::Data<HybridCache<String, V, S>>,
+    key: web::Path<String>
+) -> impl Responder
+where
+    V: Serialize + Clone + Send + Sync + 'static,
+    S: CacheSerializer<V> + Send + Sync + 'static,
+{
+    match cache.inspect(&key.into_inner()).await {
+        Ok(info) if info.in_l1 || info.in_l2 => HttpResponse::Ok().json(info),
+        Ok(_) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            tracing::error!("Failed to inspect cache key: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn purge_key<V, S>(
+    cache: web::Data<HybridCache<String, V, S>>,
+    key: web::Path<String>
+) -> impl Responder
+where
+    V: Serialize + Clone + Send + Sync + 'static,
+    S: CacheSerializer<V> + Send + Sync + 'static,
+{
+    match cache.invalidate(key.into_inner()).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            tracing::error!("Failed to purge cache key: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn purge_prefix<V, S>(
+    cache: web::Data<HybridCache<String, V, S>>,
+    prefix: web::Path<String>
+) -> impl Responder
+where
+    V: Serialize + Clone + Send + Sync + 'static,
+    S: CacheSerializer<V> + Send + Sync + 'static,
+{
+    match cache.invalidate_prefix(&prefix.into_inner()).await {
+        Ok(deleted) => HttpResponse::Ok().json(PurgeResponse { deleted }),
+        Err(e) => {
+            tracing::error!("Failed to purge cache prefix: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn purge_all<V, S>(
+    cache: web::Data<HybridCache<String, V, S>>
+) -> impl Responder
+where
+    V: Serialize + Clone + Send + Sync + 'static,
+    S: CacheSerializer<V> + Send + Sync + 'static,
+{
+    match cache.invalidate_all().await {
+        Ok(deleted) => HttpResponse::Ok().json(PurgeResponse { deleted }),
+        Err(e) => {
+            tracing::error!("Failed to purge cache: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
\ No newline at end of file
@@ -1,17 +1,22 @@
-use std::{future::{ready, Future, Ready}, marker::PhantomData, pin::Pin, rc::Rc, sync::Arc, task::{Context, Poll}};
+use std::{cell::RefCell, collections::HashSet, future::{ready, Ready}, pin::Pin, rc::Rc, sync::Arc, task::{Context, Poll}, time::{SystemTime, UNIX_EPOCH}};
 
-use actix_web::{body::{BodySize, BoxBody, EitherBody, MessageBody}, dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, http::{header::HeaderMap, StatusCode}, web::{Bytes, BytesMut}, Error, HttpMessage};
+use actix_web::{body::{BodySize, BoxBody, EitherBody, MessageBody}, dev::{forward_ready, Extensions, Payload, Service, ServiceRequest, ServiceResponse, Transform}, http::{header::{self, HeaderMap}, StatusCode}, web::{Bytes, BytesMut}, Error, HttpMessage, HttpResponse};
 use futures_util::{future::LocalBoxFuture, StreamExt};
+use base64::{engine::general_purpose, Engine as _};
 use pin_project_lite::pin_project;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::{cache::HybridCache, expiry::Expiration, serializer::bitcode::BitcodeSerializer};
+pub mod admin;
+
+use crate::{cache::{CacheError, HybridCache}, expiry::Expiration, serializer::{bitcode::BitcodeSerializer, compressed::CompressedSerializer}};
 
 pub struct RequestContext<'a> {
     pub method: &'a str,
     pub path: &'a str,
     pub query_string: &'a str,
     pub headers: &'a HeaderMap,
+    pub extensions: &'a Extensions,
     pub body: &'a LazyBody
 }
 
@@ -43,7 +48,11 @@ type CacheConditionFn = Arc<dyn Fn(&RequestContext) -> bool + Send + Sync>;
 
 type KeyGenerator = Arc<dyn Fn(&RequestContext) -> String + Send + Sync>;
 
-type Cache = HybridCache<String, CacheEntry, BitcodeSerializer<CacheEntry>>;
+type TagGenerator = Arc<dyn Fn(&RequestContext) -> Vec<String> + Send + Sync>;
+
+pub type ResponseCache = HybridCache<String, CacheEntry, CompressedSerializer<BitcodeSerializer<CacheEntry>>>;
+
+type Cache = ResponseCache;
 
 pub struct CacheMiddleware {
     cache: Cache,
@@ -53,9 +62,12 @@ pub struct CacheMiddleware {
 #[derive(Clone)]
 pub struct CacheConfig {
     ttl: Expiration,
+    soft_ttl: Option<Expiration>,
     max_cache_size: usize,
     cache_condition: CacheConditionFn,
     key_gen_fn: KeyGenerator,
+    tag_gen_fn: TagGenerator,
+    invalidate_condition: CacheConditionFn,
     requires_body: bool,
 }
 
@@ -63,15 +75,18 @@ impl Default for CacheConfig {
     fn default() -> Self {
         Self {
             ttl: Expiration::Minutes(60),
+            soft_ttl: None,
             max_cache_size: 1024 * 32,
             cache_condition: Arc::new(|_| true),
             key_gen_fn: Arc::new(generate_key_default),
+            tag_gen_fn: Arc::new(|_| Vec::new()),
+            invalidate_condition: Arc::new(|_| false),
             requires_body: false
         }
     }
 }
 
-fn generate_key_default(ctx: &RequestContext) -> String {
+pub fn generate_key_default(ctx: &RequestContext) -> String {
     format!(
         "{}_{}_{}",
         ctx.method,
@@ -80,6 +95,35 @@ fn generate_key_default(ctx: &RequestContext) -> String {
     )
 }
 
+/// Identifies the caller for [`generate_user_key`], implemented by whatever
+/// the authentication middleware stores in request extensions.
+pub trait CacheSubject: 'static {
+    fn cache_subject(&self) -> &str;
+}
+
+/// Like [`generate_key_default`], but keeps responses of different authenticated
+/// callers apart. The authentication middleware must run before [`CacheMiddleware`].
+/// Such responses should carry `Vary: Authorization`, so that they are served as `private`.
+pub fn generate_user_key<T: CacheSubject>(ctx: &RequestContext) -> String {
+    match ctx.extensions.get::<T>() {
+        Some(subject) => format!("{}_user_{}", generate_key_default(ctx), subject.cache_subject()),
+        None => generate_key_default(ctx),
+    }
+}
+
+/// Derives a tag from the first two path segments, e.g. `/books/5/chapters` -> `books_5`.
+pub fn generate_tags_default(ctx: &RequestContext) -> Vec<String> {
+    let mut segments = ctx.path
+        .split('/')
+        .filter(|segment| !segment.is_empty());
+
+    match (segments.next(), segments.next()) {
+        (Some(resource), Some(id)) => vec![format!("{}_{}", resource, id)],
+        (Some(resource), None) => vec![resource.to_string()],
+        _ => Vec::new(),
+    }
+}
+
 impl CacheMiddleware {
     pub fn new(cache: Cache) -> Self {
         Self {
@@ -100,6 +144,13 @@ impl CacheMiddleware {
         self
     }
 
+    /// Entries older than `soft_ttl` are still served, but trigger a background refresh.
+    /// `ttl` remains the hard limit after which an entry is gone.
+    pub fn soft_ttl(mut self, soft_ttl: Expiration) -> Self {
+        self.config.soft_ttl = Some(soft_ttl);
+        self
+    }
+
     pub fn max_cache_size(mut self, size: usize) -> Self {
         self.config.max_cache_size = size;
         self
@@ -121,6 +172,23 @@ impl CacheMiddleware {
         self
     }
 
+    pub fn tag_gen_fn<F>(mut self, tag_fn: F) -> Self
+    where
+        F: Fn(&RequestContext) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.config.tag_gen_fn = Arc::new(tag_fn);
+        self
+    }
+
+    /// Successful requests matching the condition evict every entry sharing their tags.
+    pub fn invalidate_condition<F>(mut self, condition: F) -> Self
+    where
+        F: Fn(&RequestContext) -> bool + Send + Sync + 'static,
+    {
+        self.config.invalidate_condition = Arc::new(condition);
+        self
+    }
+
     pub fn requires_body(mut self, requires_body: bool) -> Self {
         self.config.requires_body = requires_body;
         self
@@ -131,13 +199,201 @@ pub struct CacheMiddlewareService<S> {
     service: Rc<S>,
     cache: Cache,
     config: CacheConfig,
+    refreshing: Rc<RefCell<HashSet<String>>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct CacheEntry {
     pub status: u16,
     pub headers: Vec<(String, String)>,
-    pub body: Vec<u8>
+    pub body: Vec<u8>,
+    pub etag: String,
+    pub created_at: u64,
+    /// Lifetime in seconds after applying the response `Cache-Control`.
+    pub ttl: u64,
+    /// Set on entries that only point to their variants, see [`variant_key`].
+    pub vary: Vec<String>,
+}
+
+impl CacheEntry {
+    fn age(&self) -> u64 {
+        unix_now().saturating_sub(self.created_at)
+    }
+
+    /// Expiration of the entry when copied into L1, so that it never outlives its
+    /// Redis copy. Sliding and unlimited lifetimes are kept as configured.
+    fn local_expiry(&self, ttl: Expiration) -> Expiration {
+        match ttl {
+            Expiration::Never | Expiration::Sliding(_) if self.ttl >= ttl.get_seconds() => ttl,
+            _ => Expiration::from_seconds(self.ttl.saturating_sub(self.age())),
+        }
+    }
+
+    fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        headers.get_all(header::IF_NONE_MATCH)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.etag)
+    }
+
+    fn to_response(&self, config: &CacheConfig) -> HttpResponse {
+        let mut res = HttpResponse::build(
+            StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK)
+        );
+
+        for (header, value) in &self.headers {
+            res.append_header((header.as_str(), value.as_str()));
+        }
+
+        self.insert_validators(&mut res, config);
+
+        res.body(self.body.clone())
+    }
+
+    fn to_not_modified(&self, config: &CacheConfig) -> HttpResponse {
+        let mut res = HttpResponse::NotModified();
+
+        self.insert_validators(&mut res, config);
+
+        res.finish()
+    }
+
+    /// Responses varying on credentials belong to a single caller,
+    /// so shared caches in front of the service must not store them.
+    fn is_private(&self) -> bool {
+        self.headers.iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(header::VARY.as_str()))
+            .flat_map(|(_, value)| value.split(','))
+            .any(|name| {
+                let name = name.trim();
+                name.eq_ignore_ascii_case("authorization") || name.eq_ignore_ascii_case("cookie")
+            })
+    }
+
+    fn insert_validators(&self, res: &mut actix_web::HttpResponseBuilder, config: &CacheConfig) {
+        let age = self.age();
+        let ttl = self.ttl.min(MAX_AGE_LIMIT);
+        let visibility = if self.is_private() { "private" } else { "public" };
+
+        let cache_control = match config.soft_ttl {
+            Some(soft_ttl) if soft_ttl.get_seconds() < ttl => format!(
+                "{}, max-age={}, stale-while-revalidate={}",
+                visibility,
+                soft_ttl.get_seconds().saturating_sub(age),
+                ttl - soft_ttl.get_seconds()
+            ),
+            _ => format!("{}, max-age={}", visibility, ttl.saturating_sub(age)),
+        };
+
+        res.insert_header((header::ETAG, self.etag.as_str()))
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .insert_header((header::AGE, age.to_string()));
+    }
+}
+
+/// `Expiration::Never` is advertised to clients as one year.
+const MAX_AGE_LIMIT: u64 = 60 * 60 * 24 * 365;
+
+fn compute_etag(body: &[u8]) -> String {
+    format!("\"{}\"", general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(body)))
+}
+
+/// Derives the TTL from the response `Cache-Control`, never exceeding `ttl`.
+/// Returns `None` if the response must not be stored in a shared cache.
+fn response_ttl(headers: &HeaderMap, ttl: Expiration) -> Option<Expiration> {
+    let mut max_age = None;
+    let mut s_maxage = None;
+
+    let directives = headers.get_all(header::CACHE_CONTROL)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase());
+
+    for directive in directives {
+        match directive.split_once('=') {
+            // Repeated directives, e.g. from merged upstream responses, keep the shortest
+            Some(("max-age", value)) => max_age = min_seconds(max_age, value),
+            Some(("s-maxage", value)) => s_maxage = min_seconds(s_maxage, value),
+            Some(("private", _)) => return None,
+            None if matches!(directive.as_str(), "no-store" | "no-cache" | "private") => return None,
+            _ => (),
+        }
+    }
+
+    match s_maxage.or(max_age) {
+        Some(0) => None,
+        Some(seconds) if seconds < ttl.get_seconds() => Some(Expiration::from_seconds(seconds)),
+        _ => Some(ttl),
+    }
+}
+
+fn min_seconds(current: Option<u64>, value: &str) -> Option<u64> {
+    match (current, value.trim_matches('"').parse::<u64>().ok()) {
+        (Some(current), Some(seconds)) => Some(current.min(seconds)),
+        (current, seconds) => seconds.or(current),
+    }
+}
+
+/// Returns the lowercased header names from `Vary`, or `None` for `Vary: *`.
+fn response_vary(headers: &HeaderMap) -> Option<Vec<String>> {
+    let mut names = Vec::new();
+
+    let values = headers.get_all(header::VARY)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty());
+
+    for name in values {
+        if name == "*" {
+            return None
+        }
+        names.push(name);
+    }
+
+    names.sort();
+    names.dedup();
+
+    Some(names)
+}
+
+/// Responses with `Vary` are stored under a key derived from the request headers they
+/// depend on, while the plain key holds an entry listing those headers. Header values
+/// are hashed, so credentials never end up in Redis keys.
+fn variant_key(key: &str, vary: &[String], headers: &HeaderMap) -> String {
+    let mut hasher = Sha256::new();
+
+    for name in vary {
+        let values = headers.get_all(name.as_str())
+            .map(|value| value.as_bytes())
+            .collect::<Vec<_>>();
+
+        hasher.update(name.as_bytes());
+        hasher.update(b":");
+        hasher.update(values.join(&b',').as_slice());
+        hasher.update(b"\n");
+    }
+
+    format!("{}_vary_{}", key, general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize()))
+}
+
+async fn lookup(cache: &Cache, key: &str, ttl: Expiration, headers: &HeaderMap) -> Result<Option<CacheEntry>, CacheError> {
+    let local_expiry = |entry: &CacheEntry| entry.local_expiry(ttl);
+
+    match cache.get_with(&key.to_string(), ttl, local_expiry).await? {
+        Some(entry) if !entry.vary.is_empty() => {
+            cache.get_with(&variant_key(key, &entry.vary, headers), ttl, local_expiry).await
+        },
+        entry => Ok(entry),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
 }
 
 impl<S, B> Transform<S, ServiceRequest> for CacheMiddleware
@@ -157,82 +413,151 @@ where
             service: Rc::new(service),
             cache: self.cache.clone(),
             config: self.config.clone(),
+            refreshing: Rc::new(RefCell::new(HashSet::new())),
         }))
     }
 }
 
-pin_project! {
-    struct CacheResponseFuture<S, B>
-    where
-        B: MessageBody,
-        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
-    {
-        #[pin]
-        fut: S::Future,
-        should_cache: bool,
-        cache_key: String,
-        cache: Cache,
-        ttl: Expiration,
-        max_cache_size: usize,
-        _marker: PhantomData<B>,
-    }
-}
-
-impl<S, B> Future for CacheResponseFuture<S, B>
+/// Stores a successful response if its headers allow it. Bodies of a known size up to
+/// `max_cache_size` are buffered, so the first response already carries the validators,
+/// anything else is streamed through and stored once it has been sent.
+async fn cache_response<B>(
+    res: ServiceResponse<B>,
+    cache_key: String,
+    tags: Vec<String>,
+    cache: Cache,
+    config: &CacheConfig,
+) -> Result<ServiceResponse<EitherBody<B, BoxBody>>, Error>
 where
     B: MessageBody + 'static,
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
 {
-    type Output = Result<ServiceResponse<EitherBody<B, BoxBody>>, Error>;
+    let status = res.status();
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
+    if !status.is_success() {
+        return Ok(res.map_body(|_, b| EitherBody::left(b)));
+    }
 
-        let res = futures_util::ready!(this.fut.poll(cx))?;
+    let policy = response_ttl(res.headers(), config.ttl)
+        .zip(response_vary(res.headers()));
 
-        let status = res.status();
-        
-        if !(*this.should_cache && status.is_success()) {
-            return Poll::Ready(Ok(res.map_body(|_, b| EitherBody::left(b))));
-        }
+    let Some((ttl, vary)) = policy else {
+        return Ok(res.map_body(|_, b| EitherBody::left(b)));
+    };
+
+    let headers = res.headers()
+        .iter()
+        .filter(|(name, _)| {
+            !matches!(
+                name.as_str().to_lowercase().as_str(),
+                "connection" | "transfer-encoding" | "content-length"
+            )
+        })
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                value.to_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let variant_key = (!vary.is_empty())
+        .then(|| variant_key(&cache_key, &vary, res.request().headers()));
+
+    let target = CacheTarget {
+        cache,
+        key: cache_key,
+        variant_key,
+        vary,
+        tags,
+        ttl,
+    };
+
+    let is_buffered = matches!(
+        res.response().body().size(),
+        BodySize::Sized(size) if size > 0 && size as usize <= config.max_cache_size
+    );
 
-        let headers = res.headers().clone();
-
-        let cache_key = this.cache_key.clone();
-        let ttl = *this.ttl;
-        let max_size = *this.max_cache_size;
-        let cache = this.cache.clone();
-
-        let res = res.map_body(move |_, body| {
-            let filtered_headers = headers
-                .iter()
-                .filter(|(name, _)| {
-                    !matches!(
-                        name.as_str().to_lowercase().as_str(),
-                        "connection" | "transfer-encoding" | "content-length"
-                    )
-                })
-                .map(|(name, value)| {
-                    (
-                        name.to_string(),
-                        value.to_str().unwrap_or_default().to_string(),
-                    )
-                })
-                .collect::<Vec<_>>();
+    if !is_buffered {
+        let max_size = config.max_cache_size;
 
+        return Ok(res.map_body(move |_, body| {
             EitherBody::right(BoxBody::new(CacheableBody {
                 body: body.boxed(),
                 status: status.as_u16(),
-                headers: filtered_headers,
+                headers,
                 body_buffer: BytesMut::new(),
-                cache_key,
-                cache,
-                ttl,
+                target: Some(target),
                 max_size,
             }))
-        });
+        }));
+    }
+
+    let (req, res) = res.into_parts();
+
+    let body = actix_web::body::to_bytes(res.into_body())
+        .await
+        .map_err(|e| {
+            let e: Box<dyn std::error::Error> = e.into();
+            actix_web::error::ErrorInternalServerError(e)
+        })?;
+
+    let entry = CacheEntry {
+        status: status.as_u16(),
+        headers,
+        etag: compute_etag(&body),
+        body: body.to_vec(),
+        created_at: unix_now(),
+        ttl: ttl.get_seconds(),
+        vary: Vec::new(),
+    };
+
+    let res = if entry.is_not_modified(req.headers()) {
+        entry.to_not_modified(config)
+    } else {
+        entry.to_response(config)
+    };
+
+    actix_web::rt::spawn(target.store(entry));
+
+    Ok(ServiceResponse::new(req, res).map_body(|_, body| EitherBody::right(body)))
+}
+
+/// Where and for how long a response is stored once its body is known.
+struct CacheTarget {
+    cache: Cache,
+    key: String,
+    variant_key: Option<String>,
+    vary: Vec<String>,
+    tags: Vec<String>,
+    ttl: Expiration,
+}
 
-        Poll::Ready(Ok(res))
+impl CacheTarget {
+    async fn store(self, entry: CacheEntry) {
+        let key = match self.variant_key {
+            Some(variant_key) => {
+                let pointer = CacheEntry {
+                    status: entry.status,
+                    headers: Vec::new(),
+                    body: Vec::new(),
+                    etag: String::new(),
+                    created_at: entry.created_at,
+                    ttl: entry.ttl,
+                    vary: self.vary,
+                };
+
+                if let Err(e) = self.cache.set_with_tags(self.key, pointer, &self.tags, self.ttl).await {
+                    tracing::error!("Failed to insert value in cache: {:?}", e);
+                };
+
+                variant_key
+            },
+            None => self.key,
+        };
+
+        if let Err(e) = self.cache.set_with_tags(key, entry, &self.tags, self.ttl).await {
+            tracing::error!("Failed to insert value in cache: {:?}", e);
+        };
     }
 }
 
@@ -243,9 +568,7 @@ pin_project! {
         status: u16,
         headers: Vec<(String, String)>,
         body_buffer: BytesMut,
-        cache_key: String,
-        cache: Cache,
-        ttl: Expiration,
+        target: Option<CacheTarget>,
         max_size: usize,
     }
 
@@ -256,23 +579,21 @@ pin_project! {
             let body = this.body_buffer.clone().freeze();
 
             if !body.is_empty() && body.len() <= *this.max_size {
-                let status = *this.status;
-                let headers = this.headers.clone();
-                let cache = this.cache.clone();
-                let ttl = *this.ttl;
-                let key = this.cache_key.clone();
-
-                actix_web::rt::spawn(async move {
-                    let entry = CacheEntry {
-                        status,
-                        headers,
-                        body: body.to_vec()
-                    };
-
-                    if let Err(e) = cache.set(key, entry, ttl).await {
-                        tracing::error!("Failed to insert value in cache: {:?}", e);
-                    };
-                });
+                let Some(target) = this.target.take() else {
+                    return
+                };
+
+                let entry = CacheEntry {
+                    status: *this.status,
+                    headers: std::mem::take(this.headers),
+                    etag: compute_etag(&body),
+                    body: body.to_vec(),
+                    created_at: unix_now(),
+                    ttl: target.ttl.get_seconds(),
+                    vary: Vec::new(),
+                };
+
+                actix_web::rt::spawn(target.store(entry));
             }
         }
     }
@@ -303,6 +624,57 @@ impl MessageBody for CacheableBody {
     }
 }
 
+const CONDITIONAL_HEADERS: [header::HeaderName; 5] = [
+    header::IF_MATCH,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+    header::IF_UNMODIFIED_SINCE,
+    header::IF_RANGE,
+];
+
+/// Lets the key be refreshed again once dropped, even if the refresh panicked or never ran.
+struct RefreshGuard {
+    refreshing: Rc<RefCell<HashSet<String>>>,
+    key: String,
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        self.refreshing.borrow_mut().remove(&self.key);
+    }
+}
+
+pin_project! {
+    struct RevalidatingBody {
+        #[pin]
+        body: BoxBody,
+        on_drop: Option<Box<dyn FnOnce()>>,
+    }
+
+    impl PinnedDrop for RevalidatingBody {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some(on_drop) = this.project().on_drop.take() {
+                on_drop();
+            }
+        }
+    }
+}
+
+impl MessageBody for RevalidatingBody {
+    type Error = <BoxBody as MessageBody>::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.project().body.poll_next(cx)
+    }
+}
+
 impl<S, B> Service<ServiceRequest> for CacheMiddlewareService<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
@@ -319,6 +691,7 @@ where
         let cache = self.cache.clone();
         let config = self.config.clone();
         let service = self.service.clone();
+        let refreshing = self.refreshing.clone();
 
         Box::pin(async move {
             let body = if config.requires_body {
@@ -334,29 +707,103 @@ where
                 BytesMut::new()
             };
 
-            let ctx = RequestContext {
-                method: req.method().as_str(),
-                path: req.path(),
-                query_string: req.query_string(),
-                headers: req.headers(),
-                body: &LazyBody::new(body.clone()),
-            };
+            let (cache_key, should_cache, should_invalidate, tags) = {
+                let extensions = req.extensions();
+
+                let ctx = RequestContext {
+                    method: req.method().as_str(),
+                    path: req.path(),
+                    query_string: req.query_string(),
+                    headers: req.headers(),
+                    extensions: &extensions,
+                    body: &LazyBody::new(body.clone()),
+                };
+
+                let cache_key = (config.key_gen_fn)(&ctx);
+                let should_cache = (config.cache_condition)(&ctx);
+                let should_invalidate = (config.invalidate_condition)(&ctx);
+
+                let tags = if should_cache || should_invalidate {
+                    (config.tag_gen_fn)(&ctx)
+                } else {
+                    Vec::new()
+                };
 
-            let cache_key = (config.key_gen_fn)(&ctx);
-            let should_cache = (config.cache_condition)(&ctx);
+                (cache_key, should_cache, should_invalidate, tags)
+            };
 
             if should_cache {
-                match cache.get(&cache_key, config.ttl).await {
+                match lookup(&cache, &cache_key, config.ttl, req.headers()).await {
                     Ok(Some(entry)) => {
-                        let mut res = actix_web::HttpResponse::build(
-                            StatusCode::from_u16(entry.status).unwrap_or(StatusCode::OK)
-                        );
+                        let res = if entry.is_not_modified(req.headers()) {
+                            entry.to_not_modified(&config)
+                        } else {
+                            entry.to_response(&config)
+                        };
 
-                        for (header, value) in entry.headers {
-                            res.insert_header((header, value));
-                        }
+                        let is_stale = config.soft_ttl
+                            .is_some_and(|soft_ttl| entry.age() >= soft_ttl.get_seconds());
+
+                        if is_stale && refreshing.borrow_mut().insert(cache_key.clone()) {
+                            let guard = RefreshGuard {
+                                refreshing,
+                                key: cache_key.clone(),
+                            };
 
-                        let res = res.body(entry.body);
+                            let (http_req, payload) = req.into_parts();
+
+                            let payload = if config.requires_body {
+                                Payload::from(body.freeze())
+                            } else {
+                                payload
+                            };
+
+                            // The refresh reuses the original request, so it may only start
+                            // once the stale response has been sent and released its clone.
+                            let refresh = Box::new({
+                                let http_req = http_req.clone();
+
+                                move || {
+                                    actix_web::rt::spawn(async move {
+                                        let _guard = guard;
+
+                                        let mut req = ServiceRequest::from_parts(http_req, payload);
+
+                                        // Otherwise the client's validators could turn the refresh into a 304
+                                        for name in CONDITIONAL_HEADERS {
+                                            req.headers_mut().remove(name);
+                                        }
+
+                                        let result = service.call(req).await;
+
+                                        let result = match result {
+                                            Ok(res) => cache_response(res, cache_key, tags, cache, &config).await,
+                                            Err(e) => Err(e),
+                                        };
+
+                                        let result = match result {
+                                            Ok(res) => actix_web::body::to_bytes(res.into_body())
+                                                .await
+                                                .map(|_| ())
+                                                .map_err(Error::from),
+                                            Err(e) => Err(e),
+                                        };
+
+                                        if let Err(e) = result {
+                                            tracing::error!("Failed to refresh stale cache entry: {:?}", e);
+                                        }
+                                    });
+                                }
+                            });
+
+                            return Ok(
+                                ServiceResponse::new(http_req, res)
+                                    .map_body(|_, body| EitherBody::right(BoxBody::new(RevalidatingBody {
+                                        body: body.boxed(),
+                                        on_drop: Some(refresh),
+                                    })))
+                            );
+                        }
 
                         return Ok(
                             req.into_response(res)
@@ -372,17 +819,290 @@ where
                 req.set_payload(actix_web::dev::Payload::from(body.freeze()));
             }
 
-            let fut = CacheResponseFuture::<S, B> {
-                fut: service.call(req),
-                should_cache,
-                cache_key,
-                cache: cache,
-                ttl: config.ttl,
-                max_cache_size: config.max_cache_size,
-                _marker: PhantomData,
-            };
+            if should_invalidate {
+                let res = service.call(req).await?;
+
+                if res.status().is_success() {
+                    for tag in &tags {
+                        if let Err(e) = cache.invalidate_tag(tag).await {
+                            tracing::error!("Failed to invalidate cache tag {}: {:?}", tag, e);
+                        }
+                    }
+                }
+
+                return Ok(res.map_body(|_, body| EitherBody::left(body)));
+            }
+
+            let res = service.call(req).await?;
+
+            if !should_cache {
+                return Ok(res.map_body(|_, body| EitherBody::left(body)));
+            }
 
-            fut.await
+            cache_response(res, cache_key, tags, cache, &config).await
         })
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+
+    use actix_web::{http::header::{self, HeaderMap, HeaderValue}, test::{call_service, init_service, read_body, TestRequest}, web, App};
+
+    use crate::{serializer::compressed::Compression, store::memory::MemoryStore};
+
+    use super::*;
+
+    fn headers(name: header::HeaderName, values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        for value in values {
+            headers.append(name.clone(), HeaderValue::from_static(value));
+        }
+
+        headers
+    }
+
+    #[test]
+    fn response_ttl_defaults_to_configured_ttl() {
+        assert_eq!(response_ttl(&HeaderMap::new(), Expiration::Minutes(5)), Some(Expiration::Minutes(5)));
+    }
+
+    #[test]
+    fn response_ttl_is_capped_by_max_age() {
+        let headers = headers(header::CACHE_CONTROL, &["public, max-age=30"]);
+
+        assert_eq!(response_ttl(&headers, Expiration::Minutes(5)), Some(Expiration::from_seconds(30)));
+        assert_eq!(response_ttl(&headers, Expiration::Seconds(10)), Some(Expiration::Seconds(10)));
+    }
+
+    #[test]
+    fn response_ttl_prefers_s_maxage() {
+        let headers = headers(header::CACHE_CONTROL, &["max-age=30, s-maxage=60"]);
+
+        assert_eq!(response_ttl(&headers, Expiration::Minutes(5)), Some(Expiration::from_seconds(60)));
+    }
+
+    #[test]
+    fn response_ttl_keeps_shortest_of_merged_responses() {
+        let headers = headers(header::CACHE_CONTROL, &["max-age=120", "max-age=30"]);
+
+        assert_eq!(response_ttl(&headers, Expiration::Minutes(5)), Some(Expiration::from_seconds(30)));
+    }
+
+    #[test]
+    fn response_ttl_rejects_uncacheable_responses() {
+        for value in ["no-store", "no-cache", "private", "private=\"set-cookie\"", "max-age=0", "public, MAX-AGE=0"] {
+            let headers = headers(header::CACHE_CONTROL, &[value]);
+
+            assert_eq!(response_ttl(&headers, Expiration::Minutes(5)), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn response_vary_normalizes_names() {
+        let headers = headers(header::VARY, &["Authorization, Accept-Language", "accept-language"]);
+
+        assert_eq!(response_vary(&headers), Some(vec!["accept-language".to_string(), "authorization".to_string()]));
+        assert_eq!(response_vary(&self::headers(header::VARY, &["*"])), None);
+    }
+
+    fn entry(headers: &[(&str, &str)]) -> CacheEntry {
+        CacheEntry {
+            status: 200,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            body: b"{}".to_vec(),
+            etag: compute_etag(b"{}"),
+            created_at: unix_now(),
+            ttl: 60,
+            vary: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn shared_entries_are_public() {
+        let res = entry(&[("vary", "Accept-Language")]).to_response(&CacheConfig::default());
+
+        assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), "public, max-age=60");
+    }
+
+    #[test]
+    fn entries_varying_on_credentials_are_private() {
+        let res = entry(&[("vary", "Accept-Language"), ("vary", "authorization")]).to_response(&CacheConfig::default());
+
+        assert_eq!(res.headers().get(header::CACHE_CONTROL).unwrap(), "private, max-age=60");
+        assert_eq!(res.headers().get_all(header::VARY).count(), 2);
+    }
+
+    fn response_cache() -> ResponseCache {
+        HybridCache::with_store(
+            "responses".to_string(),
+            MemoryStore::new(),
+            100,
+            CompressedSerializer::new(BitcodeSerializer::default(), Compression::Lz4),
+        )
+    }
+
+    /// Answers `hello` with the given `Cache-Control`, counting its calls.
+    fn handler(calls: Arc<AtomicUsize>, cache_control: Option<&'static str>) -> impl Fn() -> Ready<HttpResponse> + Clone {
+        move || {
+            calls.fetch_add(1, Ordering::Relaxed);
+
+            let mut res = HttpResponse::Ok();
+            if let Some(cache_control) = cache_control {
+                res.insert_header((header::CACHE_CONTROL, cache_control));
+            }
+
+            ready(res.body("hello"))
+        }
+    }
+
+    /// Gives the spawned store of a missed response time to finish.
+    async fn stored() {
+        actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[actix_web::test]
+    async fn miss_response_carries_validators_and_is_served_from_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = init_service(
+            App::new()
+                .wrap(CacheMiddleware::new(response_cache()))
+                .route("/books", web::get().to(handler(calls.clone(), None)))
+        ).await;
+
+        let miss = call_service(&app, TestRequest::get().uri("/books").to_request()).await;
+        let etag = miss.headers().get(header::ETAG).cloned().expect("miss without ETag");
+        assert_eq!(miss.headers().get(header::CACHE_CONTROL).unwrap(), "public, max-age=3600");
+        assert_eq!(read_body(miss).await, "hello");
+
+        stored().await;
+
+        let hit = call_service(&app, TestRequest::get().uri("/books").to_request()).await;
+        assert_eq!(hit.headers().get(header::ETAG), Some(&etag));
+        assert!(hit.headers().contains_key(header::AGE));
+        assert_eq!(read_body(hit).await, "hello");
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[actix_web::test]
+    async fn upstream_max_age_limits_stored_and_advertised_ttl() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = response_cache();
+        let app = init_service(
+            App::new()
+                .wrap(CacheMiddleware::new(cache.clone()))
+                .route("/books", web::get().to(handler(calls.clone(), Some("public, max-age=30"))))
+        ).await;
+
+        let miss = call_service(&app, TestRequest::get().uri("/books").to_request()).await;
+        assert_eq!(miss.headers().get(header::CACHE_CONTROL).unwrap(), "public, max-age=30");
+
+        stored().await;
+
+        let info = cache.inspect(&"GET_/books_".to_string()).await.unwrap();
+        assert!(info.ttl.is_some_and(|ttl| ttl <= 30), "{:?}", info.ttl);
+
+        let hit = call_service(&app, TestRequest::get().uri("/books").to_request()).await;
+        let age = hit.headers().get(header::AGE).unwrap().to_str().unwrap().parse::<u64>().unwrap();
+        assert_eq!(hit.headers().get(header::CACHE_CONTROL).unwrap(), format!("public, max-age={}", 30 - age).as_str());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[actix_web::test]
+    async fn matching_if_none_match_gets_not_modified() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = init_service(
+            App::new()
+                .wrap(CacheMiddleware::new(response_cache()))
+                .route("/books", web::get().to(handler(calls.clone(), None)))
+        ).await;
+        let etag = compute_etag(b"hello");
+
+        // Validators are known on a miss as well
+        let miss = call_service(
+            &app,
+            TestRequest::get().uri("/books").insert_header((header::IF_NONE_MATCH, etag.as_str())).to_request(),
+        ).await;
+        assert_eq!(miss.status(), StatusCode::NOT_MODIFIED);
+
+        stored().await;
+
+        let hit = call_service(
+            &app,
+            TestRequest::get().uri("/books").insert_header((header::IF_NONE_MATCH, etag.as_str())).to_request(),
+        ).await;
+        assert_eq!(hit.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(hit.headers().get(header::ETAG).unwrap(), etag.as_str());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let changed = call_service(
+            &app,
+            TestRequest::get().uri("/books").insert_header((header::IF_NONE_MATCH, "\"other\"")).to_request(),
+        ).await;
+        assert_eq!(changed.status(), StatusCode::OK);
+    }
+    #[actix_web::test]
+    async fn stale_refresh_drops_client_validators() {
+        let validated = Arc::new(AtomicUsize::new(0));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = init_service(
+            App::new()
+                .wrap(CacheMiddleware::new(response_cache()).soft_ttl(Expiration::Seconds(0)))
+                .route("/books", web::get().to({
+                    let (validated, calls) = (validated.clone(), calls.clone());
+                    move |req: actix_web::HttpRequest| {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                        if req.headers().contains_key(header::IF_NONE_MATCH) {
+                            validated.fetch_add(1, Ordering::Relaxed);
+                        }
+                        ready(HttpResponse::Ok().body("hello"))
+                    }
+                }))
+        ).await;
+
+        call_service(&app, TestRequest::get().uri("/books").to_request()).await;
+        stored().await;
+
+        let stale = call_service(
+            &app,
+            TestRequest::get().uri("/books").insert_header((header::IF_NONE_MATCH, compute_etag(b"hello").as_str())).to_request(),
+        ).await;
+        assert_eq!(stale.status(), StatusCode::NOT_MODIFIED);
+        drop(stale);
+        stored().await;
+
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(validated.load(Ordering::Relaxed), 0);
+    }
+
+    #[actix_web::test]
+    async fn failed_refresh_does_not_block_later_refreshes() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = init_service(
+            App::new()
+                .wrap(CacheMiddleware::new(response_cache()).soft_ttl(Expiration::Seconds(0)))
+                .route("/books", web::get().to({
+                    let calls = calls.clone();
+                    move || {
+                        if calls.fetch_add(1, Ordering::Relaxed) == 1 {
+                            panic!("refresh failed");
+                        }
+                        ready(HttpResponse::Ok().body("hello"))
+                    }
+                }))
+        ).await;
+
+        call_service(&app, TestRequest::get().uri("/books").to_request()).await;
+        stored().await;
+
+        for _ in 0..2 {
+            let stale = call_service(&app, TestRequest::get().uri("/books").to_request()).await;
+            assert_eq!(read_body(stale).await, "hello");
+            stored().await;
+        }
+
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use std::{sync::{atomic::{AtomicU32, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use metrics::{describe_gauge, gauge};
+
+/// Tracks consecutive Redis failures. Once `failure_threshold` is reached the cache
+/// stops talking to Redis for `cool_down`, after which a single request or the
+/// background probe of [`crate::cache::HybridCache`] tries it again.
+#[derive(Clone)]
+pub(crate) struct CircuitBreaker {
+    name: String,
+    failure_threshold: u32,
+    cool_down: Duration,
+    state: Arc<BreakerState>,
+}
+
+struct BreakerState {
+    failures: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(name: String, failure_threshold: u32, cool_down: Duration) -> Self {
+        describe_gauge!("cache.circuit_breaker.open", "Whether the cache is in L1-only mode because Redis is unavailable");
+        gauge!("cache.circuit_breaker.open", "cache" => name.clone()).set(0.0);
+
+        Self {
+            name,
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+            state: Arc::new(BreakerState {
+                failures: AtomicU32::new(0),
+                open_until: Mutex::new(None),
+            }),
+        }
+    }
+
+    pub(crate) fn allow(&self) -> bool {
+        if self.state.failures.load(Ordering::Relaxed) < self.failure_threshold {
+            return true
+        }
+
+        let mut open_until = self.state.open_until.lock().unwrap();
+        let now = Instant::now();
+
+        match *open_until {
+            None => true,
+            Some(until) if now < until => false,
+            Some(_) => {
+                // Let this caller probe Redis and keep everyone else out until it reports back
+                *open_until = Some(now + self.cool_down);
+                true
+            }
+        }
+    }
+
+    pub(crate) fn record_success(&self) {
+        if self.state.failures.swap(0, Ordering::Relaxed) < self.failure_threshold {
+            return
+        }
+
+        if self.state.open_until.lock().unwrap().take().is_some() {
+            tracing::info!("Redis is reachable again, cache {} leaves L1-only mode", self.name);
+            gauge!("cache.circuit_breaker.open", "cache" => self.name.clone()).set(0.0);
+        }
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.state.open_until.lock().unwrap().is_some()
+    }
+
+    pub(crate) fn cool_down(&self) -> Duration {
+        self.cool_down
+    }
+
+    /// Returns whether this failure opened the breaker.
+    pub(crate) fn record_failure(&self) -> bool {
+        let failures = self.state.failures.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+
+        if failures < self.failure_threshold {
+            return false
+        }
+
+        let mut open_until = self.state.open_until.lock().unwrap();
+        let opened = open_until.is_none();
+
+        if opened {
+            tracing::warn!(
+                "Redis failed {} times in a row, cache {} switches to L1-only mode for {:?}",
+                failures, self.name, self.cool_down
+            );
+            gauge!("cache.circuit_breaker.open", "cache" => self.name.clone()).set(1.0);
+        }
+
+        *open_until = Some(Instant::now() + self.cool_down);
+
+        opened
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_and_reports_it_once() {
+        let breaker = CircuitBreaker::new("test".to_string(), 2, Duration::from_secs(60));
+
+        assert!(!breaker.record_failure());
+        assert!(breaker.allow());
+
+        assert!(breaker.record_failure());
+        assert!(!breaker.record_failure());
+        assert!(breaker.is_open());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let breaker = CircuitBreaker::new("test".to_string(), 2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn lets_one_probe_through_after_cool_down() {
+        let breaker = CircuitBreaker::new("test".to_string(), 1, Duration::from_millis(10));
+
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+    }
+}
\ No newline at end of file
@@ -1,11 +1,22 @@
-use std::{fmt::Display, hash::Hash, time::Instant};
+use std::{collections::HashMap, fmt::{Debug, Display}, future::{self, Future}, hash::Hash, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::{Duration, Instant}};
 
-use bb8_redis::{bb8::{Pool, PooledConnection, RunError}, redis::{AsyncCommands, FromRedisValue, RedisError, ToRedisArgs}, RedisConnectionManager};
+use bb8_redis::{bb8::{Pool, RunError}, redis::{self, Client, FromRedisValue, RedisError}, RedisConnectionManager};
+use futures_util::{stream, StreamExt};
 use metrics::{describe_counter, describe_histogram, histogram, counter};
 use moka::future::{Cache, CacheBuilder};
+use serde::Serialize;
 use thiserror::Error;
+use tokio::task::JoinHandle;
 
-use crate::{expiry::{CacheExpiry, Expiration}, serializer::CacheSerializer};
+use crate::{breaker::CircuitBreaker, expiry::{CacheExpiry, Expiration}, invalidation::InvalidationChannel, serializer::CacheSerializer, store::{redis::RedisStore, RemoteEntry, RemoteStore}};
+
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(10);
+
+const WARM_UP_CONCURRENCY: usize = 16;
 
 #[derive(Error, Debug)]
 pub enum CacheError {
@@ -14,20 +25,97 @@ pub enum CacheError {
     #[error("Redis pool error: {0}")]
     RedisPool(#[from] RunError<RedisError>),
     #[error("Redis error: {0}")]
-    Redis(#[from] RedisError)
+    Redis(#[from] RedisError),
+    #[error("Redis is unavailable, cache is in L1-only mode")]
+    Unavailable,
+}
+
+pub struct GetManyResult<K, V> {
+    pub hits: HashMap<K, V>,
+    pub misses: Vec<K>,
+}
+
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub prefix: String,
+    pub l1_entries: u64,
+    pub l1: LayerStats,
+    pub l2: LayerStats,
+}
+
+#[derive(Serialize)]
+pub struct LayerStats {
+    pub requests: u64,
+    pub hits: u64,
+    pub hit_ratio: f64,
+}
+
+#[derive(Serialize)]
+pub struct KeyInfo<V> {
+    pub in_l1: bool,
+    pub in_l2: bool,
+    /// Remaining Redis TTL in seconds, `None` for persistent or missing entries.
+    pub ttl: Option<u64>,
+    pub value: Option<V>,
+}
+
+#[derive(Clone, Copy)]
+enum Layer {
+    L1,
+    L2,
+}
+
+#[derive(Default)]
+struct LayerCounters {
+    requests: AtomicU64,
+    hits: AtomicU64,
+}
+
+impl LayerCounters {
+    fn stats(&self) -> LayerStats {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let hits = self.hits.load(Ordering::Relaxed);
+
+        LayerStats {
+            requests,
+            hits,
+            hit_ratio: if requests == 0 { 0.0 } else { hits as f64 / requests as f64 },
+        }
+    }
+}
+
+/// Mirrors the `cache.requests.total`/`cache.hits.total` metrics for this cache only.
+#[derive(Default)]
+struct Counters {
+    l1: LayerCounters,
+    l2: LayerCounters,
+}
+
+impl Counters {
+    fn layer(&self, layer: Layer) -> &LayerCounters {
+        match layer {
+            Layer::L1 => &self.l1,
+            Layer::L2 => &self.l2,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct HybridCache<K, V, S> {
     prefix: String,
+    namespace: String,
     local_cache: Cache<K, (Expiration, V)>,
-    redis_pool: Pool<RedisConnectionManager>,
+    store: Arc<dyn RemoteStore>,
     serializer: S,
+    invalidation: Option<InvalidationChannel>,
+    load_lock: Option<Duration>,
+    breaker: CircuitBreaker,
+    counters: Arc<Counters>,
 }
 
 impl<K, V, S> HybridCache<K, V, S> 
 where 
-    K: AsRef<str> + FromRedisValue + Display + ToRedisArgs + Clone + Eq + Hash + Send + Sync + 'static,
+    K: AsRef<str> + FromRedisValue + Display + Clone + Eq + Hash + Send + Sync + 'static,
     V: Clone + Send + Sync + 'static,
     S: CacheSerializer<V> + Send + Sync + 'static,
 {
@@ -36,6 +124,15 @@ where
         redis_pool: Pool<RedisConnectionManager>,
         capacity: u64,
         serializer: S,
+    ) -> Self {
+        Self::with_store(prefix, RedisStore::new(redis_pool), capacity, serializer)
+    }
+
+    pub fn with_store<R: RemoteStore + 'static>(
+        prefix: String,
+        store: R,
+        capacity: u64,
+        serializer: S,
     ) -> Self {
         let local_cache = CacheBuilder::new(capacity)
             .expire_after(CacheExpiry)
@@ -44,80 +141,477 @@ where
         describe_counter!("cache.requests.total", "Total cache requests");
         describe_counter!("cache.hits.total", "Cache hits");
         describe_histogram!("cache.operation.duration", "Cache operation duration in seconds");
+
+        let breaker = CircuitBreaker::new(prefix.clone(), DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOL_DOWN);
         
         Self {
+            namespace: prefix.clone(),
             prefix,
             local_cache,
-            redis_pool,
+            store: Arc::new(store),
             serializer,
+            invalidation: None,
+            load_lock: None,
+            breaker,
+            counters: Arc::default(),
         }
     }
 
+    /// After `failure_threshold` consecutive Redis failures the cache serves and stores
+    /// values in L1 only, retrying Redis once per `cool_down` even without traffic.
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, cool_down: Duration) -> Self {
+        self.breaker = CircuitBreaker::new(self.prefix.clone(), failure_threshold, cool_down);
+        self
+    }
+
+    /// Stores entries under `{prefix}_v{version}`, so bumping the version after changing
+    /// `V` makes the new code ignore entries written with the old layout.
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.namespace = format!("{}_v{}", self.prefix, version);
+        self
+    }
+
+    /// Coalesces [`HybridCache::get_or_load`] misses across processes: only the holder
+    /// of a short Redis lock runs the loader, the others wait for its result.
+    pub fn with_load_lock(mut self, ttl: Duration) -> Self {
+        self.load_lock = Some(ttl);
+        self
+    }
+
+    /// Keeps L1 consistent across replicas: writes and invalidations are published
+    /// on a channel named after `prefix`, and a background subscriber evicts them locally.
+    pub fn with_invalidation_channel(mut self, client: Client) -> Self {
+        let channel = InvalidationChannel::new(&self.prefix);
+        channel.spawn_listener(client, self.local_cache.clone());
+
+        self.invalidation = Some(channel);
+        self
+    }
+
+    fn count_requests(&self, layer: Layer, count: u64) {
+        let label = match layer {
+            Layer::L1 => "l1",
+            Layer::L2 => "l2",
+        };
+
+        counter!("cache.requests.total", "layer" => label).increment(count);
+        self.counters.layer(layer).requests.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn count_hits(&self, layer: Layer, count: u64) {
+        let label = match layer {
+            Layer::L1 => "l1",
+            Layer::L2 => "l2",
+        };
+
+        counter!("cache.hits.total", "layer" => label).increment(count);
+        self.counters.layer(layer).hits.fetch_add(count, Ordering::Relaxed);
+    }
+
     fn format_key(&self, key: &K) -> String {
-        format!("{}_{}", self.prefix, key)
+        format!("{}_{}", self.namespace, key)
+    }
+
+    fn format_tag(&self, tag: &str) -> String {
+        format!("{}_tag_{}", self.prefix, tag)
+    }
+
+    fn format_lock(&self, key: &K) -> String {
+        format!("{}_lock_{}", self.namespace, key)
     }
 
     pub async fn get(&self, key: &K, expiry: Expiration) -> Result<Option<V>, CacheError> {
+        self.get_with(key, expiry, |_| expiry).await
+    }
+
+    /// Like [`HybridCache::get`], but values found in Redis are copied into L1 with
+    /// the expiration returned by `local_expiry`, e.g. the remaining lifetime of the value.
+    pub async fn get_with<F>(&self, key: &K, expiry: Expiration, local_expiry: F) -> Result<Option<V>, CacheError>
+    where
+        F: FnOnce(&V) -> Expiration,
+    {
         let start_time = Instant::now();
         
-        counter!("cache.requests.total", "layer" => "l1").increment(1);
+        self.count_requests(Layer::L1, 1);
 
         if let Some((_, v)) = self.local_cache.get(key).await {
-            counter!("cache.hits.total", "layer" => "l1").increment(1);
+            self.count_hits(Layer::L1, 1);
             histogram!("cache.operation.duration", "operation" => "get", "layer" => "l1").record(start_time.elapsed().as_secs_f64());
             return Ok(Some(v))
         }
 
-        counter!("cache.requests.total", "layer" => "l2").increment(1);
+        self.count_requests(Layer::L2, 1);
+
+        let value = match self.get_remote(key, expiry.idle()).await {
+            Err(CacheError::Unavailable) => None,
+            result => result?,
+        };
+
+        if let Some(value) = &value {
+            self.count_hits(Layer::L2, 1);
+            self.local_cache.insert(key.clone(), (local_expiry(value), value.clone())).await;
+            histogram!("cache.operation.duration", "operation" => "get", "layer" => "l2").record(start_time.elapsed().as_secs_f64());
+        }
+
+        Ok(value)
+    }
+
+    /// Looks up every key in L1 and fetches the rest from Redis with a single MGET.
+    pub async fn get_many(&self, keys: &[K], expiry: Expiration) -> Result<GetManyResult<K, V>, CacheError> {
+        let start_time = Instant::now();
+
+        let mut hits = HashMap::with_capacity(keys.len());
+        let mut remaining = Vec::new();
+
+        self.count_requests(Layer::L1, keys.len() as u64);
+
+        for key in keys {
+            match self.local_cache.get(key).await {
+                Some((_, v)) => { hits.insert(key.clone(), v); },
+                None => remaining.push(key.clone()),
+            }
+        }
+
+        self.count_hits(Layer::L1, hits.len() as u64);
+
+        if remaining.is_empty() {
+            histogram!("cache.operation.duration", "operation" => "get_many", "layer" => "l1").record(start_time.elapsed().as_secs_f64());
+            return Ok(GetManyResult { hits, misses: remaining })
+        }
+
+        self.count_requests(Layer::L2, remaining.len() as u64);
+
+        let values = match self.get_many_remote(&remaining, expiry.idle()).await {
+            Ok(values) => values,
+            Err(CacheError::Unavailable) => vec![None; remaining.len()],
+            Err(e) => return Err(e),
+        };
+
+        let mut misses = Vec::new();
+
+        for (key, value) in remaining.into_iter().zip(values) {
+            match value {
+                Some(value) => {
+                    self.count_hits(Layer::L2, 1);
+                    self.local_cache.insert(key.clone(), (expiry, value.clone())).await;
+                    hits.insert(key, value);
+                },
+                None => misses.push(key),
+            }
+        }
+
+        histogram!("cache.operation.duration", "operation" => "get_many", "layer" => "l2").record(start_time.elapsed().as_secs_f64());
+
+        Ok(GetManyResult { hits, misses })
+    }
+
+    async fn get_many_remote(&self, keys: &[K], idle: Option<Duration>) -> Result<Vec<Option<V>>, CacheError> {
+        let formatted_keys = keys
+            .iter()
+            .map(|key| self.format_key(key))
+            .collect::<Vec<_>>();
 
-        let mut con = self.get_redis_connection().await?;
+        let result = match idle {
+            Some(idle) => self.remote()?.mget_ex(&formatted_keys, idle).await,
+            None => self.remote()?.mget(&formatted_keys).await,
+        };
+        let values = self.observe(result)?;
 
-        let result = con
-            .get::<String, Option<Vec<u8>>>(self.format_key(key))
+        let mut bad_keys = Vec::new();
+
+        let values = values
+            .into_iter()
+            .zip(formatted_keys)
+            .map(|(value, key)| {
+                let value = value?;
+                match self.serializer.deserialize(&value) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        tracing::warn!("Failed to deserialize value from Redis for key {}, treating it as a miss: {:#?}", key, e);
+                        bad_keys.push(key);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let _ = self.store.del(&bad_keys).await; // Delete bad data
+
+        Ok(values)
+    }
+
+    /// Returns the cached value or runs `loader` to produce it. Concurrent misses
+    /// for the same key within the process share a single loader call.
+    /// Cache failures are logged and fall through to the loader.
+    pub async fn get_or_load<F, Fut, E>(&self, key: &K, expiry: Expiration, loader: F) -> Result<V, Arc<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+        E: Send + Sync + 'static,
+    {
+        let start_time = Instant::now();
+
+        self.count_requests(Layer::L1, 1);
+
+        if let Some((_, v)) = self.local_cache.get(key).await {
+            self.count_hits(Layer::L1, 1);
+            histogram!("cache.operation.duration", "operation" => "get", "layer" => "l1").record(start_time.elapsed().as_secs_f64());
+            return Ok(v)
+        }
+
+        let result = self.local_cache
+            .try_get_with(key.clone(), async {
+                self.load(key, expiry, loader)
+                    .await
+                    .map(|value| (expiry, value))
+            })
             .await;
 
-        let duration = start_time.elapsed().as_secs_f64();
+        histogram!("cache.operation.duration", "operation" => "get_or_load").record(start_time.elapsed().as_secs_f64());
+
+        result.map(|(_, v)| v)
+    }
+
+    /// Populates both layers for `keys` in a background task, calling `loader` for
+    /// keys missing from Redis. The handle resolves to the number of keys warmed.
+    pub fn warm_up<F, Fut, E>(&self, keys: Vec<K>, expiry: Expiration, loader: F) -> JoinHandle<usize>
+    where
+        S: Clone,
+        F: Fn(K) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<V, E>> + Send + 'static,
+        E: Debug + Send + Sync + 'static,
+    {
+        let cache = self.clone();
+
+        tokio::spawn(async move {
+            let start_time = Instant::now();
+            let total = keys.len();
+            let cache = &cache;
+            let loader = &loader;
+
+            let warmed = stream::iter(keys)
+                .map(|key| async move {
+                    match cache.get_or_load(&key, expiry, || loader(key.clone())).await {
+                        Ok(_) => true,
+                        Err(e) => {
+                            tracing::warn!("Failed to warm up key {} in cache {}: {:?}", key, cache.prefix, e);
+                            false
+                        },
+                    }
+                })
+                .buffer_unordered(WARM_UP_CONCURRENCY)
+                .filter(|warmed| future::ready(*warmed))
+                .count()
+                .await;
+
+            tracing::info!(
+                "Warmed up {}/{} keys in cache {} in {:?}",
+                warmed,
+                total,
+                cache.prefix,
+                start_time.elapsed()
+            );
+
+            warmed
+        })
+    }
+
+    async fn load<F, Fut, E>(&self, key: &K, expiry: Expiration, loader: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        self.count_requests(Layer::L2, 1);
+
+        match self.get_remote(key, expiry.idle()).await {
+            Ok(Some(value)) => {
+                self.count_hits(Layer::L2, 1);
+                return Ok(value)
+            },
+            Ok(None) | Err(CacheError::Unavailable) => (),
+            Err(e) => tracing::error!("Failed to get value from cache for key {}: {:?}", key, e),
+        };
+
+        let locked = match self.load_lock {
+            Some(ttl) => match self.try_lock(key, ttl).await {
+                Ok(true) => true,
+                Ok(false) => {
+                    if let Some(value) = self.wait_for_remote(key, ttl, expiry.idle()).await {
+                        return Ok(value)
+                    }
+                    false
+                },
+                Err(CacheError::Unavailable) => false,
+                Err(e) => {
+                    tracing::error!("Failed to acquire load lock for key {}: {:?}", key, e);
+                    false
+                },
+            },
+            None => false,
+        };
+
+        let result = loader().await;
+
+        if let Ok(value) = &result {
+            match self.set_remote(key, value, &[], expiry).await {
+                Ok(()) | Err(CacheError::Unavailable) => (),
+                Err(e) => tracing::error!("Failed to insert value in cache for key {}: {:?}", key, e),
+            }
+        }
+
+        if locked {
+            if let Err(e) = self.unlock(key).await {
+                tracing::error!("Failed to release load lock for key {}: {:?}", key, e);
+            }
+        }
+
+        result
+    }
+
+    async fn try_lock(&self, key: &K, ttl: Duration) -> Result<bool, CacheError> {
+        let result = self.remote()?.set_nx(&self.format_lock(key), ttl).await;
+        self.observe(result)
+    }
+
+    async fn unlock(&self, key: &K) -> Result<(), CacheError> {
+        let result = self.remote()?.del(&[self.format_lock(key)]).await;
+        self.observe(result)
+    }
+
+    async fn wait_for_remote(&self, key: &K, ttl: Duration, idle: Option<Duration>) -> Option<V> {
+        let deadline = Instant::now() + ttl;
+
+        while Instant::now() < deadline {
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+
+            match self.get_remote(key, idle).await {
+                Ok(Some(value)) => return Some(value),
+                Ok(None) => (),
+                Err(_) => return None,
+            }
+        }
+
+        None
+    }
+
+    /// Reads the key from Redis, extending its expiration by `idle` when set.
+    async fn get_remote(&self, key: &K, idle: Option<Duration>) -> Result<Option<V>, CacheError> {
+        let result = match idle {
+            Some(idle) => self.remote()?.get_ex(&self.format_key(key), idle).await,
+            None => self.remote()?.get(&self.format_key(key)).await,
+        };
 
-        match result {
+        match self.observe(result) {
             Ok(Some(value)) => {
-                counter!("cache.hits.total", "layer" => "l2").increment(1);
                 let result = self.serializer.deserialize(&value);
                 match result {
-                    Ok(value) => {
-                        self.local_cache.insert(key.clone(), (expiry, value.clone())).await;
-                        histogram!("cache.operation.duration", "operation" => "get", "layer" => "l2").record(duration);
-                        Ok(Some(value))
-                    }
+                    Ok(value) => Ok(Some(value)),
                     Err(e) => {
-                        tracing::error!("Failed to deserialize value from Redis for key {}: {:#?}", key, e);
-                        let _ = con.del::<_, ()>(self.format_key(key)).await; // Delete bad data
-                        Err(e)
+                        // Most likely written by a build with a different layout of `V`
+                        tracing::warn!("Failed to deserialize value from Redis for key {}, treating it as a miss: {:#?}", key, e);
+                        let _ = self.store.del(&[self.format_key(key)]).await; // Delete bad data
+                        Ok(None)
                     },
                 }
             }
             Ok(None) => Ok(None),
             Err(e) => {
                 tracing::error!("Failed to get value from redis: {:#?}", e);
-                Err(CacheError::Redis(e))
+                Err(e)
             }
         }
     }
 
     pub async fn set(&self, key: K, value: V, expiry: Expiration) -> Result<(), CacheError> {
+        self.set_with_tags(key, value, &[], expiry).await
+    }
+
+    /// Stores the value and registers its key in a Redis set per tag,
+    /// so that it can be evicted later with [`HybridCache::invalidate_tag`].
+    pub async fn set_with_tags(&self, key: K, value: V, tags: &[String], expiry: Expiration) -> Result<(), CacheError> {
         let start_time = Instant::now();
 
         self.local_cache.insert(key.clone(), (expiry, value.clone())).await;
-        
-        let mut con = self.get_redis_connection().await?;
 
-        let encoded = self.serializer.serialize(&value)?;
-        con.set_ex::<_, _, ()>(self.format_key(&key), encoded, expiry.get_seconds()).await?;
+        match self.set_remote(&key, &value, tags, expiry).await {
+            Ok(()) | Err(CacheError::Unavailable) => (),
+            Err(e) => return Err(e),
+        };
         
         histogram!("cache.operation.duration", "operation" => "set").record(start_time.elapsed().as_secs_f64());
 
         Ok(())
     }
 
+    /// Stores all values in L1 and writes them to Redis in a single pipeline.
+    pub async fn set_many(&self, entries: Vec<(K, V)>, expiry: Expiration) -> Result<(), CacheError> {
+        let start_time = Instant::now();
+
+        for (key, value) in &entries {
+            self.local_cache.insert(key.clone(), (expiry, value.clone())).await;
+        }
+
+        match self.set_many_remote(&entries, expiry).await {
+            Ok(()) | Err(CacheError::Unavailable) => (),
+            Err(e) => return Err(e),
+        };
+
+        histogram!("cache.operation.duration", "operation" => "set_many").record(start_time.elapsed().as_secs_f64());
+
+        Ok(())
+    }
+
+    async fn set_many_remote(&self, entries: &[(K, V)], expiry: Expiration) -> Result<(), CacheError> {
+        let entries = entries
+            .iter()
+            .map(|(key, value)| self.make_entry(key, value, &[], expiry))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.write_remote(entries).await
+    }
+
+    async fn set_remote(&self, key: &K, value: &V, tags: &[String], expiry: Expiration) -> Result<(), CacheError> {
+        let entry = self.make_entry(key, value, tags, expiry)?;
+
+        self.write_remote(vec![entry]).await
+    }
+
+    async fn write_remote(&self, entries: Vec<RemoteEntry>) -> Result<(), CacheError> {
+        if entries.is_empty() {
+            return Ok(())
+        }
+
+        let result = self.remote()?.set_ex(&entries).await;
+        self.observe(result)?;
+
+        let members = entries
+            .into_iter()
+            .map(|entry| entry.member)
+            .collect::<Vec<_>>();
+
+        self.publish(&members).await
+    }
+
+    fn make_entry(&self, key: &K, value: &V, tags: &[String], expiry: Expiration) -> Result<RemoteEntry, CacheError> {
+        Ok(RemoteEntry {
+            key: self.format_key(key),
+            value: self.serializer.serialize(value)?,
+            tags: tags.iter().map(|tag| self.format_tag(tag)).collect(),
+            member: key.to_string(),
+            ttl: expiry.ttl(),
+        })
+    }
+
+    async fn publish(&self, keys: &[String]) -> Result<(), CacheError> {
+        match &self.invalidation {
+            Some(channel) => {
+                let result = self.remote()?.publish(channel.channel(), keys).await;
+                self.observe(result)
+            },
+            None => Ok(()),
+        }
+    }
+
     pub async fn invalidate(&self, key: K) -> Result<(), CacheError> {
         let start_time = Instant::now();
 
@@ -125,21 +619,477 @@ where
             .invalidate(&key)
             .await;
 
-        let mut con = self.get_redis_connection().await?;
-        con.del::<_, ()>(self.format_key(&key)).await?;
+        match self.invalidate_remote(&key).await {
+            Ok(()) | Err(CacheError::Unavailable) => (),
+            Err(e) => return Err(e),
+        };
 
         histogram!("cache.operation.duration", "operation" => "invalidate").record(start_time.elapsed().as_secs_f64());
 
         Ok(())
     }
 
-    async fn get_redis_connection(&self) -> Result<PooledConnection<'_, RedisConnectionManager>, CacheError> {
-        match self.redis_pool.get().await {
-            Ok(con) => Ok(con),
-            Err(e) => {
-                tracing::error!("Failed to get redis connection: {e:?}");
-                Err(CacheError::RedisPool(e))
+    async fn invalidate_remote(&self, key: &K) -> Result<(), CacheError> {
+        let result = self.remote()?.del(&[self.format_key(key)]).await;
+        self.observe(result)?;
+
+        self.publish(&[key.to_string()]).await
+    }
+
+    /// Evicts every entry tagged with `tag` from both layers.
+    /// While Redis is unavailable the tagged keys are unknown, so all of L1 is dropped.
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<(), CacheError> {
+        let start_time = Instant::now();
+
+        match self.invalidate_tag_remote(tag).await {
+            Ok(keys) => {
+                for key in keys {
+                    self.local_cache.invalidate(&key).await;
+                }
+            },
+            Err(CacheError::Unavailable) => self.local_cache.invalidate_all(),
+            Err(e) => return Err(e),
+        };
+
+        histogram!("cache.operation.duration", "operation" => "invalidate_tag").record(start_time.elapsed().as_secs_f64());
+
+        Ok(())
+    }
+
+    /// Removes the tagged entries from Redis and returns their keys.
+    async fn invalidate_tag_remote(&self, tag: &str) -> Result<Vec<K>, CacheError> {
+        let result = self.remote()?.take_tag(&self.format_tag(tag)).await;
+        let members = self.observe(result)?;
+
+        let keys = members
+            .iter()
+            .filter_map(|member| {
+                match redis::from_redis_value::<K>(redis::Value::BulkString(member.clone().into_bytes())) {
+                    Ok(key) => Some(key),
+                    Err(e) => {
+                        tracing::error!("Failed to parse tagged key {}: {:?}", member, e);
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let formatted_keys = keys
+            .iter()
+            .map(|key| self.format_key(key))
+            .collect::<Vec<_>>();
+
+        let result = self.remote()?.del(&formatted_keys).await;
+        self.observe(result)?;
+
+        self.publish(&members).await?;
+
+        Ok(keys)
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        self.local_cache.run_pending_tasks().await;
+
+        CacheStats {
+            prefix: self.prefix.clone(),
+            l1_entries: self.local_cache.entry_count(),
+            l1: self.counters.l1.stats(),
+            l2: self.counters.l2.stats(),
+        }
+    }
+
+    /// Looks the key up in both layers without populating L1.
+    pub async fn inspect(&self, key: &K) -> Result<KeyInfo<V>, CacheError> {
+        let local = self.local_cache.get(key).await.map(|(_, v)| v);
+
+        let remote = self.get_remote(key, None).await?;
+
+        let ttl = match remote {
+            Some(_) => {
+                let result = self.remote()?.ttl(&self.format_key(key)).await;
+                self.observe(result)?.map(|ttl| ttl.as_secs())
+            },
+            None => None,
+        };
+
+        Ok(KeyInfo {
+            in_l1: local.is_some(),
+            in_l2: remote.is_some(),
+            ttl,
+            value: local.or(remote),
+        })
+    }
+
+    /// Evicts every key starting with `prefix` from both layers.
+    /// Returns the number of entries removed from Redis.
+    pub async fn invalidate_prefix(&self, prefix: &str) -> Result<usize, CacheError> {
+        let start_time = Instant::now();
+
+        let local_keys = self.local_cache
+            .iter()
+            .filter(|(key, _)| (**key).as_ref().starts_with(prefix))
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+
+        for key in local_keys {
+            self.local_cache.invalidate(&*key).await;
+        }
+
+        let deleted = self.delete_remote_prefix(&format!("{}_{}", self.namespace, prefix)).await?;
+
+        histogram!("cache.operation.duration", "operation" => "invalidate_prefix").record(start_time.elapsed().as_secs_f64());
+
+        Ok(deleted)
+    }
+
+    /// Drops both layers, including tag sets and load locks.
+    /// Returns the number of keys removed from Redis.
+    pub async fn invalidate_all(&self) -> Result<usize, CacheError> {
+        let start_time = Instant::now();
+
+        self.local_cache.invalidate_all();
+
+        let deleted = self.delete_remote_prefix(&format!("{}_", self.prefix)).await?;
+
+        histogram!("cache.operation.duration", "operation" => "invalidate_all").record(start_time.elapsed().as_secs_f64());
+
+        Ok(deleted)
+    }
+
+    async fn delete_remote_prefix(&self, prefix: &str) -> Result<usize, CacheError> {
+        let result = self.remote()?.delete_prefix(prefix).await;
+        let deleted = self.observe(result)?;
+
+        let namespace = format!("{}_", self.namespace);
+
+        let members = deleted
+            .iter()
+            .filter_map(|key| key.strip_prefix(&namespace))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        self.publish(&members).await?;
+
+        Ok(deleted.len())
+    }
+
+    fn observe<T>(&self, result: Result<T, CacheError>) -> Result<T, CacheError> {
+        match &result {
+            Ok(_) => self.breaker.record_success(),
+            Err(e) if is_unhealthy(e) => {
+                if self.breaker.record_failure() {
+                    self.spawn_probe();
+                }
+            },
+            Err(_) => (),
+        }
+
+        result
+    }
+
+    /// Pings Redis once per cool-down while the breaker is open, so that the cache
+    /// leaves L1-only mode as soon as Redis is back, not on the next request.
+    fn spawn_probe(&self) {
+        let breaker = self.breaker.clone();
+        let store = self.store.clone();
+
+        tokio::spawn(async move {
+            while breaker.is_open() {
+                tokio::time::sleep(breaker.cool_down()).await;
+
+                match store.ping().await {
+                    Ok(()) => breaker.record_success(),
+                    Err(e) if is_unhealthy(&e) => { breaker.record_failure(); },
+                    Err(e) => tracing::warn!("Unexpected error while probing Redis: {:?}", e),
+                }
             }
+        });
+    }
+
+    fn remote(&self) -> Result<&dyn RemoteStore, CacheError> {
+        if !self.breaker.allow() {
+            return Err(CacheError::Unavailable)
         }
+
+        Ok(self.store.as_ref())
+    }
+}
+
+/// Whether the error says Redis is unreachable. Type mismatches and script errors
+/// do not say anything about Redis health.
+fn is_unhealthy(e: &CacheError) -> bool {
+    match e {
+        CacheError::RedisPool(_) => true,
+        CacheError::Redis(e) => e.is_io_error() || e.is_timeout() || e.is_connection_dropped() || e.is_connection_refusal(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+    use async_trait::async_trait;
+
+    use crate::{serializer::json::JsonSerializer, store::memory::MemoryStore};
+
+    use super::*;
+
+    const TTL: Expiration = Expiration::Minutes(1);
+
+    fn cache(store: &MemoryStore) -> HybridCache<String, String, JsonSerializer<String>> {
+        HybridCache::with_store("test".to_string(), store.clone(), 100, JsonSerializer::default())
+    }
+
+    #[tokio::test]
+    async fn values_are_shared_through_the_store() {
+        let store = MemoryStore::new();
+        let (a, b) = (cache(&store), cache(&store));
+
+        a.set("key".to_string(), "value".to_string(), TTL).await.unwrap();
+
+        assert_eq!(b.get(&"key".to_string(), TTL).await.unwrap(), Some("value".to_string()));
+
+        let info = b.inspect(&"key".to_string()).await.unwrap();
+        assert!(info.in_l1 && info.in_l2);
+        assert!(info.ttl.is_some_and(|ttl| ttl <= 60));
+    }
+
+    #[tokio::test]
+    async fn invalidate_tag_evicts_tagged_entries_from_both_layers() {
+        let cache = cache(&MemoryStore::new());
+
+        cache.set_with_tags("a".to_string(), "a".to_string(), &["books".to_string()], TTL).await.unwrap();
+        cache.set_with_tags("b".to_string(), "b".to_string(), &["books".to_string()], TTL).await.unwrap();
+        cache.set_with_tags("c".to_string(), "c".to_string(), &["authors".to_string()], TTL).await.unwrap();
+
+        cache.invalidate_tag("books").await.unwrap();
+
+        for key in ["a", "b"] {
+            let info = cache.inspect(&key.to_string()).await.unwrap();
+            assert!(!info.in_l1 && !info.in_l2, "{} was not evicted", key);
+        }
+
+        assert_eq!(cache.get(&"c".to_string(), TTL).await.unwrap(), Some("c".to_string()));
+    }
+
+    #[tokio::test]
+    async fn get_or_load_coalesces_concurrent_misses() {
+        let cache = cache(&MemoryStore::new());
+        let loads = AtomicUsize::new(0);
+
+        let load = || async {
+            loads.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok::<_, String>("value".to_string())
+        };
+
+        let key = "key".to_string();
+        let (first, second) = tokio::join!(
+            cache.get_or_load(&key, TTL, load),
+            cache.get_or_load(&key, TTL, load),
+        );
+
+        assert_eq!(first.unwrap(), "value");
+        assert_eq!(second.unwrap(), "value");
+        assert_eq!(loads.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn get_many_combines_both_layers() {
+        let store = MemoryStore::new();
+        let (a, b) = (cache(&store), cache(&store));
+
+        a.set("remote".to_string(), "remote".to_string(), TTL).await.unwrap();
+        b.set("local".to_string(), "local".to_string(), TTL).await.unwrap();
+
+        let keys = ["local", "remote", "missing"].map(str::to_string);
+        let result = b.get_many(&keys, TTL).await.unwrap();
+
+        assert_eq!(result.hits.len(), 2);
+        assert_eq!(result.misses, vec!["missing".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn bumping_the_version_ignores_old_entries() {
+        let store = MemoryStore::new();
+
+        cache(&store).set("key".to_string(), "value".to_string(), TTL).await.unwrap();
+
+        let cache = cache(&store).with_version(2);
+        assert_eq!(cache.get(&"key".to_string(), TTL).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn undecodable_entries_are_deleted_misses() {
+        let store = MemoryStore::new();
+        store.set_ex(&[RemoteEntry {
+            key: "test_key".to_string(),
+            value: b"not json".to_vec(),
+            tags: Vec::new(),
+            ttl: None,
+            member: "key".to_string(),
+        }]).await.unwrap();
+
+        assert_eq!(cache(&store).get(&"key".to_string(), TTL).await.unwrap(), None);
+        assert_eq!(store.get("test_key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn invalidate_prefix_spares_other_keys() {
+        let cache = cache(&MemoryStore::new());
+
+        cache.set("books_1".to_string(), "1".to_string(), TTL).await.unwrap();
+        cache.set("authors_1".to_string(), "1".to_string(), TTL).await.unwrap();
+
+        assert_eq!(cache.invalidate_prefix("books_").await.unwrap(), 1);
+
+        assert!(!cache.inspect(&"books_1".to_string()).await.unwrap().in_l1);
+        assert!(cache.inspect(&"authors_1".to_string()).await.unwrap().in_l2);
+    }
+
+    /// Fails every call with a connection error while `down` is set.
+    #[derive(Clone, Default)]
+    struct FlakyStore {
+        inner: MemoryStore,
+        down: Arc<AtomicBool>,
+    }
+
+    impl FlakyStore {
+        fn check(&self) -> Result<(), CacheError> {
+            if self.down.load(Ordering::Relaxed) {
+                let e = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "down");
+                return Err(CacheError::Redis(e.into()))
+            }
+
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl RemoteStore for FlakyStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, CacheError> {
+            self.check()?;
+            self.inner.get(key).await
+        }
+
+        async fn mget(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, CacheError> {
+            self.check()?;
+            self.inner.mget(keys).await
+        }
+
+        async fn get_ex(&self, key: &str, ttl: Duration) -> Result<Option<Vec<u8>>, CacheError> {
+            self.check()?;
+            self.inner.get_ex(key, ttl).await
+        }
+
+        async fn mget_ex(&self, keys: &[String], ttl: Duration) -> Result<Vec<Option<Vec<u8>>>, CacheError> {
+            self.check()?;
+            self.inner.mget_ex(keys, ttl).await
+        }
+
+        async fn set_ex(&self, entries: &[RemoteEntry]) -> Result<(), CacheError> {
+            self.check()?;
+            self.inner.set_ex(entries).await
+        }
+
+        async fn del(&self, keys: &[String]) -> Result<(), CacheError> {
+            self.check()?;
+            self.inner.del(keys).await
+        }
+
+        async fn set_nx(&self, key: &str, ttl: Duration) -> Result<bool, CacheError> {
+            self.check()?;
+            self.inner.set_nx(key, ttl).await
+        }
+
+        async fn take_tag(&self, tag: &str) -> Result<Vec<String>, CacheError> {
+            self.check()?;
+            self.inner.take_tag(tag).await
+        }
+
+        async fn publish(&self, channel: &str, messages: &[String]) -> Result<(), CacheError> {
+            self.check()?;
+            self.inner.publish(channel, messages).await
+        }
+
+        async fn ttl(&self, key: &str) -> Result<Option<Duration>, CacheError> {
+            self.check()?;
+            self.inner.ttl(key).await
+        }
+
+        async fn delete_prefix(&self, prefix: &str) -> Result<Vec<String>, CacheError> {
+            self.check()?;
+            self.inner.delete_prefix(prefix).await
+        }
+
+        async fn ping(&self) -> Result<(), CacheError> {
+            self.check()
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_store_switches_to_l1_only() {
+        let store = FlakyStore::default();
+        let cache = HybridCache::with_store("test".to_string(), store.clone(), 100, JsonSerializer::<String>::default())
+            .with_circuit_breaker(2, Duration::from_secs(60));
+
+        store.down.store(true, Ordering::Relaxed);
+
+        for _ in 0..2 {
+            assert!(cache.get(&"key".to_string(), TTL).await.is_err());
+        }
+
+        assert!(cache.breaker.is_open());
+
+        // Served from L1 without touching the store
+        cache.set("key".to_string(), "value".to_string(), TTL).await.unwrap();
+        assert_eq!(cache.get(&"key".to_string(), TTL).await.unwrap(), Some("value".to_string()));
+        assert_eq!(cache.get(&"missing".to_string(), TTL).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn invalidations_succeed_in_l1_only_mode() {
+        let store = FlakyStore::default();
+        let cache = HybridCache::with_store("test".to_string(), store.clone(), 100, JsonSerializer::<String>::default())
+            .with_circuit_breaker(1, Duration::from_secs(60));
+
+        cache.set_with_tags("a".to_string(), "a".to_string(), &["books".to_string()], TTL).await.unwrap();
+        cache.set("b".to_string(), "b".to_string(), TTL).await.unwrap();
+        cache.set("c".to_string(), "c".to_string(), TTL).await.unwrap();
+
+        // The failure that opens the breaker is still reported
+        store.down.store(true, Ordering::Relaxed);
+        assert!(cache.invalidate("c".to_string()).await.is_err());
+        assert!(cache.breaker.is_open());
+
+        cache.set("c".to_string(), "c".to_string(), TTL).await.unwrap();
+        cache.invalidate("c".to_string()).await.unwrap();
+        assert_eq!(cache.get(&"c".to_string(), TTL).await.unwrap(), None);
+        assert_eq!(cache.get(&"b".to_string(), TTL).await.unwrap(), Some("b".to_string()));
+
+        // Without the tag sets, entries can't be told apart
+        cache.invalidate_tag("books").await.unwrap();
+        assert_eq!(cache.get(&"a".to_string(), TTL).await.unwrap(), None);
+        assert_eq!(cache.get(&"b".to_string(), TTL).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn background_probe_closes_breaker_without_traffic() {
+        let store = FlakyStore::default();
+        let cache = HybridCache::with_store("test".to_string(), store.clone(), 100, JsonSerializer::<String>::default())
+            .with_circuit_breaker(1, Duration::from_millis(20));
+
+        store.down.store(true, Ordering::Relaxed);
+        assert!(cache.get(&"key".to_string(), TTL).await.is_err());
+        assert!(cache.breaker.is_open());
+
+        // Failed probes keep it open
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(cache.breaker.is_open());
+
+        store.down.store(false, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!cache.breaker.is_open());
     }
 }
\ No newline at end of file
@@ -4,28 +4,78 @@ use moka::Expiry;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Expiration {
+    /// Stored without a TTL in both layers
     Never,
     Seconds(u16),
     Minutes(u8),
     Days(u8),
+    Duration(Duration),
+    /// Expires after `ttl` plus a random delay of up to `jitter`, picked on every write,
+    /// so that entries written together don't expire at the same moment.
+    Jittered { ttl: Duration, jitter: Duration },
+    /// Expires once the entry hasn't been read for the given duration.
+    /// Hits in L1 and L2 both extend the lifetime.
+    Sliding(Duration),
 }
 
 impl Expiration {
+    /// Adds up to `jitter` of random delay to a fixed expiration.
+    /// `Never` and `Sliding` expirations are returned unchanged.
+    pub fn with_jitter(self, jitter: Duration) -> Self {
+        match (self, self.as_duration()) {
+            (Expiration::Sliding(_), _) | (_, None) => self,
+            (_, Some(ttl)) => Expiration::Jittered { ttl, jitter },
+        }
+    }
+
+    /// Turns a fixed expiration into a time-to-idle of the same length.
+    pub fn sliding(self) -> Self {
+        match self.as_duration() {
+            Some(idle) => Expiration::Sliding(idle),
+            None => self,
+        }
+    }
+
+    /// Nominal lifetime of an entry, without jitter.
     pub fn as_duration(&self) -> Option<Duration> {
         match self {
             Expiration::Never => None,
             Expiration::Seconds(s) => Some(Duration::from_secs(*s as u64)),
             Expiration::Minutes(m) => Some(Duration::from_secs(60 * *m as u64)),
-            Expiration::Days(d) => Some(Duration::from_secs(*d as u64 * 60 * 60 * 24))
+            Expiration::Days(d) => Some(Duration::from_secs(*d as u64 * 60 * 60 * 24)),
+            Expiration::Duration(d) => Some(*d),
+            Expiration::Jittered { ttl, .. } => Some(*ttl),
+            Expiration::Sliding(idle) => Some(*idle),
         }
     }
 
-    pub fn get_seconds(&self) -> u64 {
+    /// TTL to apply to a fresh write, with jitter resolved.
+    pub fn ttl(&self) -> Option<Duration> {
+        match self {
+            Expiration::Jittered { ttl, jitter } if !jitter.is_zero() => {
+                let jitter = rand::random_range(0..=jitter.as_millis() as u64);
+                Some(*ttl + Duration::from_millis(jitter))
+            },
+            _ => self.as_duration(),
+        }
+    }
+
+    /// Duration a read extends the entry by, for sliding expirations.
+    pub fn idle(&self) -> Option<Duration> {
         match self {
-            Expiration::Never => u64::MAX,
-            Expiration::Seconds(s) => *s as u64,
-            Expiration::Minutes(m) => *m as u64 * 60,
-            Expiration::Days(d) => *d as u64 * 60 * 60 * 24,
+            Expiration::Sliding(idle) => Some(*idle),
+            _ => None,
+        }
+    }
+
+    pub fn from_seconds(seconds: u64) -> Self {
+        Expiration::Duration(Duration::from_secs(seconds))
+    }
+
+    pub fn get_seconds(&self) -> u64 {
+        match self.as_duration() {
+            Some(duration) => duration.as_secs(),
+            None => u64::MAX,
         }
     }
 }
@@ -43,6 +93,88 @@ where
         value: &(Expiration, V),
         _current_time: Instant,
     ) -> Option<Duration> {
-        value.0.as_duration()
+        value.0.ttl()
+    }
+
+    fn expire_after_read(
+        &self,
+        _key: &K,
+        value: &(Expiration, V),
+        _read_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        _last_modified_at: Instant,
+    ) -> Option<Duration> {
+        value.0.idle().or(duration_until_expiry)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &K,
+        value: &(Expiration, V),
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        value.0.ttl()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_expirations_have_matching_durations() {
+        assert_eq!(Expiration::Seconds(30).ttl(), Some(Duration::from_secs(30)));
+        assert_eq!(Expiration::Minutes(2).ttl(), Some(Duration::from_secs(120)));
+        assert_eq!(Expiration::Days(1).ttl(), Some(Duration::from_secs(86_400)));
+        assert_eq!(Expiration::from_seconds(5).get_seconds(), 5);
+        assert_eq!(Expiration::Seconds(30).idle(), None);
+    }
+
+    #[test]
+    fn never_has_no_ttl() {
+        assert_eq!(Expiration::Never.ttl(), None);
+        assert_eq!(Expiration::Never.get_seconds(), u64::MAX);
+        assert_eq!(Expiration::Never.with_jitter(Duration::from_secs(1)), Expiration::Never);
+        assert_eq!(Expiration::Never.sliding(), Expiration::Never);
+    }
+
+    #[test]
+    fn jittered_ttl_stays_within_jitter() {
+        let expiry = Expiration::Minutes(1).with_jitter(Duration::from_secs(10));
+
+        assert_eq!(expiry.as_duration(), Some(Duration::from_secs(60)));
+
+        for _ in 0..100 {
+            let ttl = expiry.ttl().unwrap();
+            assert!(ttl >= Duration::from_secs(60) && ttl <= Duration::from_secs(70), "{:?}", ttl);
+        }
+    }
+
+    #[test]
+    fn sliding_expirations_extend_on_read() {
+        let expiry = Expiration::Minutes(1).sliding();
+
+        assert_eq!(expiry, Expiration::Sliding(Duration::from_secs(60)));
+        assert_eq!(expiry.idle(), Some(Duration::from_secs(60)));
+        assert_eq!(expiry.ttl(), Some(Duration::from_secs(60)));
+        assert_eq!(expiry.with_jitter(Duration::from_secs(10)), expiry);
+    }
+
+    #[test]
+    fn l1_expiry_follows_variant() {
+        let sliding = (Expiration::Sliding(Duration::from_secs(60)), ());
+        let fixed = (Expiration::Seconds(60), ());
+        let now = Instant::now();
+        let remaining = Some(Duration::from_secs(10));
+
+        assert_eq!(
+            Expiry::<String, _>::expire_after_read(&CacheExpiry, &"key".to_string(), &sliding, now, remaining, now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            Expiry::<String, _>::expire_after_read(&CacheExpiry, &"key".to_string(), &fixed, now, remaining, now),
+            remaining
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,135 @@
+use std::{collections::hash_map::RandomState, hash::{BuildHasher, Hash}, sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use bb8_redis::redis::{Client, FromRedisValue, RedisError};
+use futures_util::StreamExt;
+use moka::future::Cache;
+
+use crate::expiry::Expiration;
+
+static INSTANCE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Every instance publishes on its own `{prefix}_invalidation:{id}` channel and
+/// listens on the `{prefix}_invalidation:*` pattern, skipping its own messages.
+#[derive(Clone)]
+pub(crate) struct InvalidationChannel {
+    channel: String,
+    pattern: String,
+}
+
+impl InvalidationChannel {
+    pub(crate) fn new(prefix: &str) -> Self {
+        let id = RandomState::new().hash_one(INSTANCE_COUNTER.fetch_add(1, Ordering::Relaxed));
+
+        Self {
+            channel: format!("{}_invalidation:{:016x}", prefix, id),
+            pattern: format!("{}_invalidation:*", prefix),
+        }
+    }
+
+    pub(crate) fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    pub(crate) fn spawn_listener<K, V>(&self, client: Client, local_cache: Cache<K, (Expiration, V)>)
+    where
+        K: FromRedisValue + Eq + Hash + Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static,
+    {
+        let channel = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = channel.listen(&client, &local_cache).await {
+                    tracing::error!("Cache invalidation listener on {} failed: {:?}", channel.pattern, e);
+                }
+
+                // Messages may have been missed while disconnected
+                local_cache.invalidate_all();
+
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    async fn listen<K, V>(&self, client: &Client, local_cache: &Cache<K, (Expiration, V)>) -> Result<(), RedisError>
+    where
+        K: FromRedisValue + Eq + Hash + Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static,
+    {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.psubscribe(&self.pattern).await?;
+
+        let mut messages = pubsub.on_message();
+
+        while let Some(msg) = messages.next().await {
+            if msg.get_channel_name() == self.channel {
+                continue;
+            }
+
+            match msg.get_payload::<K>() {
+                Ok(key) => local_cache.invalidate(&key).await,
+                Err(e) => tracing::error!("Failed to parse invalidation message: {:?}", e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bb8_redis::{bb8::Pool, redis::Client, RedisConnectionManager};
+
+    use crate::{cache::HybridCache, expiry::Expiration, serializer::json::JsonSerializer};
+
+    const TTL: Expiration = Expiration::Minutes(1);
+
+    fn redis_url() -> String {
+        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string())
+    }
+
+    async fn cache(prefix: &str) -> HybridCache<String, String, JsonSerializer<String>> {
+        let manager = RedisConnectionManager::new(redis_url()).unwrap();
+        let pool = Pool::builder().build(manager).await.unwrap();
+
+        HybridCache::new(prefix.to_string(), pool, 100, JsonSerializer::default())
+            .with_invalidation_channel(Client::open(redis_url()).unwrap())
+    }
+
+    /// Gives the listeners time to subscribe and receive published messages.
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local Redis"]
+    async fn invalidations_evict_entries_from_other_instances() {
+        let prefix = format!("invalidation_test_{}", std::process::id());
+        let (a, b) = (cache(&prefix).await, cache(&prefix).await);
+        settle().await;
+
+        a.set_with_tags("tagged".to_string(), "value".to_string(), &["tag".to_string()], TTL).await.unwrap();
+        a.set("plain".to_string(), "value".to_string(), TTL).await.unwrap();
+        settle().await;
+
+        for key in ["tagged", "plain"] {
+            assert_eq!(b.get(&key.to_string(), TTL).await.unwrap(), Some("value".to_string()));
+            assert!(b.inspect(&key.to_string()).await.unwrap().in_l1);
+        }
+
+        a.invalidate("plain".to_string()).await.unwrap();
+        a.invalidate_tag("tag").await.unwrap();
+        settle().await;
+
+        for key in ["tagged", "plain"] {
+            let info = b.inspect(&key.to_string()).await.unwrap();
+            assert!(!info.in_l1 && !info.in_l2, "{} is still cached by the other instance", key);
+        }
+
+        a.invalidate_all().await.unwrap();
+    }
+}
\ No newline at end of file
@@ -1,6 +1,10 @@
 pub mod cache;
 pub mod expiry;
 pub mod serializer;
+pub mod store;
+
+mod breaker;
+mod invalidation;
 
 #[cfg(feature = "actix-web")]
 pub mod actix;
\ No newline at end of file
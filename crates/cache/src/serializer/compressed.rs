@@ -0,0 +1,162 @@
+use crate::cache::CacheError;
+
+use super::CacheSerializer;
+
+const DEFAULT_THRESHOLD: usize = 1024;
+
+const HEADER_RAW: u8 = 0;
+const HEADER_ZSTD: u8 = 1;
+const HEADER_LZ4: u8 = 2;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Compression {
+    Zstd(i32),
+    Lz4,
+}
+
+/// Compresses the output of `S` once it reaches `threshold` bytes.
+/// Every payload starts with a header byte naming its format, so values written
+/// with a different algorithm or threshold can still be read back.
+#[derive(Clone)]
+pub struct CompressedSerializer<S> {
+    inner: S,
+    compression: Compression,
+    threshold: usize,
+}
+
+impl<S> CompressedSerializer<S> {
+    pub fn new(inner: S, compression: Compression) -> Self {
+        Self {
+            inner,
+            compression,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<(u8, Vec<u8>), CacheError> {
+        let result = match self.compression {
+            Compression::Zstd(level) => zstd::encode_all(data, level).map(|data| (HEADER_ZSTD, data)),
+            Compression::Lz4 => lz4::block::compress(data, None, true).map(|data| (HEADER_LZ4, data)),
+        };
+
+        result.map_err(|e| CacheError::Serialization(e.to_string()))
+    }
+}
+
+impl<V, S> CacheSerializer<V> for CompressedSerializer<S>
+where
+    S: CacheSerializer<V>,
+{
+    fn serialize(&self, value: &V) -> Result<Vec<u8>, CacheError> {
+        let data = self.inner.serialize(value)?;
+
+        let (header, payload) = if data.len() >= self.threshold {
+            match self.compress(&data)? {
+                (header, compressed) if compressed.len() < data.len() => (header, compressed),
+                _ => (HEADER_RAW, data),
+            }
+        } else {
+            (HEADER_RAW, data)
+        };
+
+        let mut result = Vec::with_capacity(payload.len() + 1);
+        result.push(header);
+        result.extend_from_slice(&payload);
+
+        Ok(result)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<V, CacheError> {
+        let (header, payload) = data
+            .split_first()
+            .ok_or_else(|| CacheError::Serialization("Empty payload".to_string()))?;
+
+        let decompressed = match *header {
+            HEADER_RAW => return self.inner.deserialize(payload),
+            HEADER_ZSTD => zstd::decode_all(payload),
+            HEADER_LZ4 => lz4::block::decompress(payload, None),
+            header => return Err(CacheError::Serialization(format!("Unknown compression header: {}", header))),
+        };
+
+        let decompressed = decompressed.map_err(|e| CacheError::Serialization(e.to_string()))?;
+
+        self.inner.deserialize(&decompressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::json::JsonSerializer;
+
+    use super::*;
+
+    fn serializer(compression: Compression) -> CompressedSerializer<JsonSerializer<String>> {
+        CompressedSerializer::new(JsonSerializer::default(), compression).threshold(64)
+    }
+
+    fn large_value() -> String {
+        "chapter ".repeat(512)
+    }
+
+    #[test]
+    fn small_values_are_stored_raw() {
+        for compression in [Compression::Zstd(3), Compression::Lz4] {
+            let serializer = serializer(compression);
+            let data = serializer.serialize(&"short".to_string()).unwrap();
+
+            assert_eq!(data[0], HEADER_RAW);
+            assert_eq!(&data[1..], b"\"short\"");
+            assert_eq!(serializer.deserialize(&data).unwrap(), "short");
+        }
+    }
+
+    #[test]
+    fn large_values_round_trip() {
+        for (compression, header) in [(Compression::Zstd(3), HEADER_ZSTD), (Compression::Lz4, HEADER_LZ4)] {
+            let serializer = serializer(compression);
+            let data = serializer.serialize(&large_value()).unwrap();
+
+            assert_eq!(data[0], header);
+            assert!(data.len() < large_value().len());
+            assert_eq!(serializer.deserialize(&data).unwrap(), large_value());
+        }
+    }
+
+    #[test]
+    fn reads_values_written_with_another_algorithm() {
+        let data = serializer(Compression::Zstd(3)).serialize(&large_value()).unwrap();
+
+        assert_eq!(serializer(Compression::Lz4).deserialize(&data).unwrap(), large_value());
+    }
+
+    #[test]
+    fn rejects_empty_and_unknown_payloads() {
+        let serializer = serializer(Compression::Lz4);
+
+        assert!(serializer.deserialize(&[]).is_err());
+        assert!(serializer.deserialize(&[9, b'"', b'"']).is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_payloads() {
+        for compression in [Compression::Zstd(3), Compression::Lz4] {
+            let serializer = serializer(compression);
+            let data = serializer.serialize(&large_value()).unwrap();
+
+            let truncated = &data[..data.len() / 2];
+            assert!(serializer.deserialize(truncated).is_err(), "{:?}", compression);
+
+            // Keeps the header and lz4's size prefix, garbles the rest
+            let mut garbled = data.clone();
+            for byte in &mut garbled[5..] {
+                *byte = !*byte;
+            }
+            assert!(serializer.deserialize(&garbled).is_err(), "{:?}", compression);
+        }
+    }
+}
\ No newline at end of file
@@ -2,6 +2,8 @@ use crate::cache::CacheError;
 
 pub mod json;
 pub mod bitcode;
+#[cfg(feature = "compression")]
+pub mod compressed;
 
 pub trait CacheSerializer<V> {
     fn serialize(&self, value: &V) -> Result<Vec<u8>, CacheError>;
@@ -0,0 +1,316 @@
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use async_trait::async_trait;
+
+use crate::cache::CacheError;
+
+use super::{RemoteEntry, RemoteStore};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Process-local store for tests and single-node deployments, holding at most `capacity` keys.
+/// Expired keys are swept on writes, and a full store evicts the key closest to expiring.
+/// Publishing is a no-op since there are no other instances to notify.
+#[derive(Clone)]
+pub struct MemoryStore {
+    inner: Arc<Mutex<MemoryStoreInner>>,
+}
+
+struct StoredValue {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+    tags: Vec<String>,
+    member: String,
+}
+
+impl StoredValue {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+struct MemoryStoreInner {
+    values: HashMap<String, StoredValue>,
+    tags: HashMap<String, HashSet<String>>,
+    capacity: usize,
+    last_sweep: Instant,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MemoryStoreInner {
+                values: HashMap::new(),
+                tags: HashMap::new(),
+                capacity: capacity.max(1),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStoreInner {
+    fn get(&mut self, key: &str) -> Option<Vec<u8>> {
+        let expired = self.values
+            .get(key)
+            .is_some_and(|stored| stored.is_expired(Instant::now()));
+
+        if expired {
+            self.remove(key);
+            return None
+        }
+
+        self.values.get(key).map(|stored| stored.value.clone())
+    }
+
+    fn get_ex(&mut self, key: &str, ttl: Duration) -> Option<Vec<u8>> {
+        let value = self.get(key)?;
+
+        if let Some(stored) = self.values.get_mut(key) {
+            stored.expires_at = Some(Instant::now() + ttl);
+        }
+
+        Some(value)
+    }
+
+    fn insert(&mut self, key: String, stored: StoredValue) {
+        self.remove(&key);
+
+        let now = Instant::now();
+
+        if self.values.len() >= self.capacity || now.duration_since(self.last_sweep) >= SWEEP_INTERVAL {
+            self.sweep(now);
+        }
+
+        if self.values.len() >= self.capacity {
+            self.evict();
+        }
+
+        for tag in &stored.tags {
+            self.tags
+                .entry(tag.clone())
+                .or_default()
+                .insert(stored.member.clone());
+        }
+
+        self.values.insert(key, stored);
+    }
+
+    /// Removes the key along with its tag memberships.
+    fn remove(&mut self, key: &str) -> Option<StoredValue> {
+        let stored = self.values.remove(key)?;
+
+        for tag in &stored.tags {
+            if let Some(members) = self.tags.get_mut(tag) {
+                members.remove(&stored.member);
+
+                if members.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+
+        Some(stored)
+    }
+
+    fn sweep(&mut self, now: Instant) {
+        let expired = self.values
+            .iter()
+            .filter(|(_, stored)| stored.is_expired(now))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for key in expired {
+            self.remove(&key);
+        }
+
+        self.last_sweep = now;
+    }
+
+    /// Makes room by dropping the key closest to expiring, persistent keys go last.
+    fn evict(&mut self) {
+        let key = self.values
+            .iter()
+            .min_by_key(|(_, stored)| (stored.expires_at.is_none(), stored.expires_at))
+            .map(|(key, _)| key.clone());
+
+        if let Some(key) = key {
+            self.remove(&key);
+        }
+    }
+}
+
+#[async_trait]
+impl RemoteStore for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, CacheError> {
+        Ok(self.inner.lock().unwrap().get(key))
+    }
+
+    async fn mget(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        Ok(keys.iter().map(|key| inner.get(key)).collect())
+    }
+
+    async fn get_ex(&self, key: &str, ttl: Duration) -> Result<Option<Vec<u8>>, CacheError> {
+        Ok(self.inner.lock().unwrap().get_ex(key, ttl))
+    }
+
+    async fn mget_ex(&self, keys: &[String], ttl: Duration) -> Result<Vec<Option<Vec<u8>>>, CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        Ok(keys.iter().map(|key| inner.get_ex(key, ttl)).collect())
+    }
+
+    async fn set_ex(&self, entries: &[RemoteEntry]) -> Result<(), CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        for entry in entries {
+            inner.insert(entry.key.clone(), StoredValue {
+                value: entry.value.clone(),
+                expires_at: entry.ttl.map(|ttl| Instant::now() + ttl),
+                tags: entry.tags.clone(),
+                member: entry.member.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn del(&self, keys: &[String]) -> Result<(), CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        for key in keys {
+            inner.remove(key);
+        }
+
+        Ok(())
+    }
+
+    async fn set_nx(&self, key: &str, ttl: Duration) -> Result<bool, CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.get(key).is_some() {
+            return Ok(false)
+        }
+
+        inner.insert(key.to_string(), StoredValue {
+            value: Vec::new(),
+            expires_at: Some(Instant::now() + ttl),
+            tags: Vec::new(),
+            member: String::new(),
+        });
+
+        Ok(true)
+    }
+
+    async fn take_tag(&self, tag: &str) -> Result<Vec<String>, CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        Ok(inner.tags.remove(tag).map(|members| members.into_iter().collect()).unwrap_or_default())
+    }
+
+    async fn publish(&self, _channel: &str, _messages: &[String]) -> Result<(), CacheError> {
+        Ok(())
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.get(key).is_none() {
+            return Ok(None)
+        }
+
+        let expires_at = inner.values.get(key).and_then(|stored| stored.expires_at);
+
+        Ok(expires_at.map(|expires_at| expires_at.saturating_duration_since(Instant::now())))
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<Vec<String>, CacheError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let keys = inner.values
+            .keys()
+            .chain(inner.tags.keys())
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for key in &keys {
+            inner.remove(key);
+            inner.tags.remove(key);
+        }
+
+        Ok(keys)
+    }
+
+    async fn ping(&self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, ttl: Option<Duration>, tags: &[&str]) -> RemoteEntry {
+        RemoteEntry {
+            key: format!("test_{}", key),
+            value: key.as_bytes().to_vec(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ttl,
+            member: key.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn full_store_sweeps_expired_keys_and_their_tags() {
+        let store = MemoryStore::with_capacity(2);
+
+        store.set_ex(&[entry("a", Some(Duration::from_millis(10)), &["tag"])]).await.unwrap();
+        store.set_ex(&[entry("b", None, &[])]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        store.set_ex(&[entry("c", None, &[])]).await.unwrap();
+
+        let inner = store.inner.lock().unwrap();
+        assert_eq!(inner.values.len(), 2);
+        assert!(!inner.values.contains_key("test_a"));
+        assert!(inner.tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn full_store_evicts_key_closest_to_expiring() {
+        let store = MemoryStore::with_capacity(2);
+
+        store.set_ex(&[entry("a", None, &[])]).await.unwrap();
+        store.set_ex(&[entry("b", Some(Duration::from_secs(60)), &[])]).await.unwrap();
+        store.set_ex(&[entry("c", Some(Duration::from_secs(120)), &[])]).await.unwrap();
+
+        assert_eq!(store.get("test_a").await.unwrap(), Some(b"a".to_vec()));
+        assert_eq!(store.get("test_b").await.unwrap(), None);
+        assert_eq!(store.get("test_c").await.unwrap(), Some(b"c".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn overwriting_a_key_updates_its_tags() {
+        let store = MemoryStore::new();
+
+        store.set_ex(&[entry("a", None, &["old"])]).await.unwrap();
+        store.set_ex(&[entry("a", None, &["new"])]).await.unwrap();
+
+        assert!(store.take_tag("old").await.unwrap().is_empty());
+        assert_eq!(store.take_tag("new").await.unwrap(), vec!["a".to_string()]);
+    }
+}
\ No newline at end of file
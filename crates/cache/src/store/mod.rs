@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::cache::CacheError;
+
+pub mod memory;
+pub mod redis;
+
+pub struct RemoteEntry {
+    pub key: String,
+    pub value: Vec<u8>,
+    pub tags: Vec<String>,
+    /// `None` keeps the entry until it is deleted.
+    pub ttl: Option<Duration>,
+    /// Added to every tag set, so that the key can be recovered on invalidation.
+    pub member: String,
+}
+
+/// L2 backend of [`crate::cache::HybridCache`]. Keys, tags and members are passed
+/// already prefixed by the cache.
+#[async_trait]
+pub trait RemoteStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, CacheError>;
+
+    async fn mget(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, CacheError>;
+
+    /// Like [`RemoteStore::get`], but resets the expiration of a found key to `ttl`.
+    async fn get_ex(&self, key: &str, ttl: Duration) -> Result<Option<Vec<u8>>, CacheError>;
+
+    /// Like [`RemoteStore::mget`], but resets the expiration of found keys to `ttl`.
+    async fn mget_ex(&self, keys: &[String], ttl: Duration) -> Result<Vec<Option<Vec<u8>>>, CacheError>;
+
+    async fn set_ex(&self, entries: &[RemoteEntry]) -> Result<(), CacheError>;
+
+    async fn del(&self, keys: &[String]) -> Result<(), CacheError>;
+
+    /// Sets the key only if it does not exist yet, returns whether it was set.
+    async fn set_nx(&self, key: &str, ttl: Duration) -> Result<bool, CacheError>;
+
+    /// Removes the tag set and returns its members.
+    async fn take_tag(&self, tag: &str) -> Result<Vec<String>, CacheError>;
+
+    async fn publish(&self, channel: &str, messages: &[String]) -> Result<(), CacheError>;
+
+    /// Remaining time to live, `None` if the key is missing or persistent.
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, CacheError>;
+
+    /// Deletes every key starting with `prefix` and returns them.
+    async fn delete_prefix(&self, prefix: &str) -> Result<Vec<String>, CacheError>;
+
+    /// Checks that the store is reachable.
+    async fn ping(&self) -> Result<(), CacheError>;
+}
@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bb8_redis::{bb8::{Pool, PooledConnection}, redis::{self, AsyncCommands}, RedisConnectionManager};
+
+use crate::cache::CacheError;
+
+use super::{RemoteEntry, RemoteStore};
+
+const SCAN_BATCH_SIZE: usize = 500;
+
+#[derive(Clone)]
+pub struct RedisStore {
+    pool: Pool<RedisConnectionManager>,
+}
+
+impl RedisStore {
+    pub fn new(pool: Pool<RedisConnectionManager>) -> Self {
+        Self { pool }
+    }
+
+    async fn get_connection(&self) -> Result<PooledConnection<'_, RedisConnectionManager>, CacheError> {
+        match self.pool.get().await {
+            Ok(con) => Ok(con),
+            Err(e) => {
+                tracing::error!("Failed to get redis connection: {e:?}");
+                Err(CacheError::RedisPool(e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RemoteStore for RedisStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, CacheError> {
+        let mut con = self.get_connection().await?;
+
+        Ok(con.get(key).await?)
+    }
+
+    async fn mget(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, CacheError> {
+        if keys.is_empty() {
+            return Ok(Vec::new())
+        }
+
+        let mut con = self.get_connection().await?;
+
+        let values = redis::cmd("MGET")
+            .arg(keys)
+            .query_async(&mut *con)
+            .await?;
+
+        Ok(values)
+    }
+
+    async fn get_ex(&self, key: &str, ttl: Duration) -> Result<Option<Vec<u8>>, CacheError> {
+        let mut con = self.get_connection().await?;
+
+        let value = redis::cmd("GETEX")
+            .arg(key)
+            .arg("PX")
+            .arg(as_millis(ttl))
+            .query_async(&mut *con)
+            .await?;
+
+        Ok(value)
+    }
+
+    async fn mget_ex(&self, keys: &[String], ttl: Duration) -> Result<Vec<Option<Vec<u8>>>, CacheError> {
+        if keys.is_empty() {
+            return Ok(Vec::new())
+        }
+
+        let mut con = self.get_connection().await?;
+
+        let mut pipe = redis::pipe();
+
+        for key in keys {
+            pipe.cmd("GETEX").arg(key).arg("PX").arg(as_millis(ttl));
+        }
+
+        let values = pipe.query_async(&mut *con).await?;
+
+        Ok(values)
+    }
+
+    async fn set_ex(&self, entries: &[RemoteEntry]) -> Result<(), CacheError> {
+        if entries.is_empty() {
+            return Ok(())
+        }
+
+        let mut con = self.get_connection().await?;
+
+        let mut pipe = redis::pipe();
+
+        for entry in entries {
+            // A plain SET also clears any TTL left from a previous write
+            match entry.ttl {
+                Some(ttl) => pipe.pset_ex(&entry.key, &entry.value, as_millis(ttl)).ignore(),
+                None => pipe.set(&entry.key, &entry.value).ignore(),
+            };
+
+            for tag in &entry.tags {
+                pipe.sadd(tag, &entry.member).ignore();
+
+                // The tag set must live at least as long as the longest entry in it
+                match entry.ttl {
+                    Some(ttl) => {
+                        pipe.cmd("PEXPIRE").arg(tag).arg(as_millis(ttl)).arg("NX").ignore();
+                        pipe.cmd("PEXPIRE").arg(tag).arg(as_millis(ttl)).arg("GT").ignore();
+                    },
+                    None => {
+                        pipe.persist(tag).ignore();
+                    },
+                };
+            }
+        }
+
+        pipe.query_async::<()>(&mut *con).await?;
+
+        Ok(())
+    }
+
+    async fn del(&self, keys: &[String]) -> Result<(), CacheError> {
+        if keys.is_empty() {
+            return Ok(())
+        }
+
+        let mut con = self.get_connection().await?;
+        con.del::<_, ()>(keys).await?;
+
+        Ok(())
+    }
+
+    async fn set_nx(&self, key: &str, ttl: Duration) -> Result<bool, CacheError> {
+        let mut con = self.get_connection().await?;
+
+        let result: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut *con)
+            .await?;
+
+        Ok(result.is_some())
+    }
+
+    async fn take_tag(&self, tag: &str) -> Result<Vec<String>, CacheError> {
+        let mut con = self.get_connection().await?;
+
+        let (members,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .smembers(tag)
+            .del(tag).ignore()
+            .query_async(&mut *con)
+            .await?;
+
+        Ok(members)
+    }
+
+    async fn publish(&self, channel: &str, messages: &[String]) -> Result<(), CacheError> {
+        if messages.is_empty() {
+            return Ok(())
+        }
+
+        let mut con = self.get_connection().await?;
+
+        let mut pipe = redis::pipe();
+
+        for message in messages {
+            pipe.cmd("PUBLISH").arg(channel).arg(message).ignore();
+        }
+
+        pipe.query_async::<()>(&mut *con).await?;
+
+        Ok(())
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, CacheError> {
+        let mut con = self.get_connection().await?;
+
+        // -2 for missing keys, -1 for keys without expiration
+        let ttl: i64 = con.pttl(key).await?;
+
+        Ok(u64::try_from(ttl).ok().map(Duration::from_millis))
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<Vec<String>, CacheError> {
+        let mut con = self.get_connection().await?;
+
+        let pattern = format!("{}*", escape_pattern(prefix));
+
+        let mut deleted = Vec::new();
+        let mut cursor = 0u64;
+
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(SCAN_BATCH_SIZE)
+                .query_async(&mut *con)
+                .await?;
+
+            if !keys.is_empty() {
+                redis::cmd("UNLINK")
+                    .arg(&keys)
+                    .query_async::<()>(&mut *con)
+                    .await?;
+
+                deleted.extend(keys);
+            }
+
+            if next == 0 {
+                break
+            }
+
+            cursor = next;
+        }
+
+        Ok(deleted)
+    }
+
+    async fn ping(&self) -> Result<(), CacheError> {
+        let mut con = self.get_connection().await?;
+
+        redis::cmd("PING").query_async::<()>(&mut *con).await?;
+
+        Ok(())
+    }
+}
+
+/// Redis rejects a zero expiration, so sub-millisecond TTLs are rounded up.
+fn as_millis(ttl: Duration) -> u64 {
+    (ttl.as_millis() as u64).max(1)
+}
+
+fn escape_pattern(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
@@ -1,28 +1,58 @@
+use opentelemetry::global;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
 use tracing::{subscriber::set_global_default, Subscriber};
-use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
+use tracing_bunyan_formatter::JsonStorageLayer;
 use tracing_log::LogTracer;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, reload, EnvFilter, Registry};
 
+use crate::{
+    log_filter::LogFilterHandle,
+    output::LogSettings,
+    redaction::{RedactingSubscriber, Redactor},
+};
+
+pub mod log_filter;
+pub mod metrics;
+pub mod otel;
+pub mod output;
+pub mod redaction;
+
+/// Logs are written to `sink`, and to rotated files if configured, in the format from `log`.
+/// Sensitive values are redacted before any layer sees them, exported spans included.
+/// Spans are additionally exported over OTLP when a `tracer_provider` is given,
+/// see [`otel::init_tracer_provider`]. The returned handle changes the filter at runtime.
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
-) -> impl Subscriber + Send + Sync
+    log: &LogSettings,
+    tracer_provider: Option<&SdkTracerProvider>,
+) -> (impl Subscriber + Send + Sync, LogFilterHandle)
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
+    let otel_layer = tracer_provider
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(otel::tracer(provider, &name)));
+    let formatting_layers = log.layers(&name, sink);
+    let redactor = Redactor::new(&log.redaction).expect("Invalid redaction pattern");
 
-    Registry::default()
+    let subscriber = Registry::default()
         .with(env_filter)
+        .with(otel_layer)
         .with(JsonStorageLayer)
-        .with(formatting_layer)
+        .with(formatting_layers);
+
+    (RedactingSubscriber::new(subscriber, redactor), LogFilterHandle::new(filter_handle))
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to set logger");
 
+    // Lets TracingLogger pick up incoming `traceparent` headers
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use actix_web::{web::{self, Data}, HttpResponse, Responder, Scope};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing_subscriber::{filter::ParseError, reload, EnvFilter, Registry};
+
+#[derive(Debug, Error)]
+pub enum LogFilterError {
+    #[error("Invalid filter directives: {0}")]
+    Parse(#[from] ParseError),
+    #[error("Failed to reload filter: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+/// Handle to the `EnvFilter` installed by [`crate::get_subscriber`].
+#[derive(Clone)]
+pub struct LogFilterHandle {
+    inner: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogFilterHandle {
+    pub(crate) fn new(inner: reload::Handle<EnvFilter, Registry>) -> Self {
+        Self { inner }
+    }
+
+    pub fn current(&self) -> Result<String, LogFilterError> {
+        Ok(self.inner.with_current(|filter| filter.to_string())?)
+    }
+
+    /// Replaces the filter, e.g. with `info,cache=debug,sqlx=debug`.
+    pub fn set(&self, directives: &str) -> Result<(), LogFilterError> {
+        let filter = EnvFilter::try_new(directives)?;
+        self.inner.reload(filter)?;
+
+        tracing::info!("Log filter changed to {}", directives);
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LogFilterSchema {
+    pub filter: String,
+}
+
+/// GET and PUT of the current filter for a `web::Data<LogFilterHandle>` registered in the app.
+/// Callers are responsible for protecting the scope.
+pub fn scope(path: &str) -> Scope {
+    web::scope(path)
+        .route("", web::get().to(get_log_filter))
+        .route("", web::put().to(set_log_filter))
+}
+
+async fn get_log_filter(handle: Data<LogFilterHandle>) -> impl Responder {
+    match handle.current() {
+        Ok(filter) => HttpResponse::Ok().json(LogFilterSchema { filter }),
+        Err(e) => {
+            tracing::error!("Failed to read log filter: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn set_log_filter(
+    handle: Data<LogFilterHandle>,
+    body: web::Json<LogFilterSchema>
+) -> impl Responder {
+    match handle.set(&body.filter) {
+        Ok(()) => HttpResponse::Ok().json(body.into_inner()),
+        Err(LogFilterError::Parse(e)) => HttpResponse::BadRequest().body(e.to_string()),
+        Err(e) => {
+            tracing::error!("Failed to set log filter: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
\ No newline at end of file
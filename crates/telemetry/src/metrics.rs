@@ -0,0 +1,99 @@
+use std::{future::{ready, Ready}, time::Instant};
+
+use ::metrics::{counter, describe_counter, describe_histogram, histogram};
+use actix_web::{dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, web::Data, Error, HttpResponse, Responder};
+use futures_util::future::LocalBoxFuture;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Installs the global Prometheus recorder, labelling every metric with `service`.
+/// The returned handle is meant to be registered as app data for [`metrics_handler`].
+pub fn install_recorder(service: &str) -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .add_global_label("service", service)
+        .set_buckets_for_metric(Matcher::Prefix("http".to_string()), DURATION_BUCKETS)
+        .expect("Failed to set histogram buckets")
+        .install_recorder()
+        .expect("Failed to install metrics recorder");
+
+    describe_counter!("http.server.requests.total", "Total HTTP requests");
+    describe_histogram!("http.server.request.duration", "HTTP request duration in seconds");
+
+    handle
+}
+
+pub async fn metrics_handler(handle: Data<PrometheusHandle>) -> impl Responder {
+    let metrics = handle.render();
+    HttpResponse::Ok().content_type("text/plain").body(metrics)
+}
+
+/// Records request count and latency labelled by method, route pattern and status class.
+/// Requests that match no route share the `unmatched` route label.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestMetricsService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsService { service }))
+    }
+}
+
+pub struct RequestMetricsService<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start_time = Instant::now();
+        let method = req.method().to_string();
+        let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let result = fut.await;
+
+            let status = match &result {
+                Ok(res) => res.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+
+            let status = format!("{}xx", status.as_u16() / 100);
+
+            counter!(
+                "http.server.requests.total",
+                "method" => method.clone(),
+                "route" => route.clone(),
+                "status" => status.clone()
+            ).increment(1);
+            histogram!(
+                "http.server.request.duration",
+                "method" => method,
+                "route" => route,
+                "status" => status
+            ).record(start_time.elapsed().as_secs_f64());
+
+            result
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::{Sampler, SdkTracer, SdkTracerProvider}, Resource};
+use serde::Deserialize;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OtlpSettings {
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`
+    pub endpoint: String,
+    /// `service.name` of exported spans, defaults to the crate name of the service
+    pub service_name: Option<String>,
+    /// Share of traces started by this service that get recorded.
+    /// Requests with an incoming `traceparent` follow the caller's decision.
+    #[serde(default = "default_sampling_ratio")]
+    pub sampling_ratio: f64,
+}
+
+fn default_sampling_ratio() -> f64 {
+    1.0
+}
+
+/// Builds a provider exporting spans in batches to the OTLP collector
+/// and installs it as the global one. `default_service_name` applies unless
+/// the settings name the service, usually `env!("CARGO_PKG_NAME")`.
+pub fn init_tracer_provider(default_service_name: &str, settings: &OtlpSettings) -> Result<SdkTracerProvider, ExporterBuildError> {
+    let service_name = settings.service_name.as_deref().unwrap_or(default_service_name);
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(settings.endpoint.clone())
+        .build()?;
+
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(settings.sampling_ratio)));
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(sampler)
+        .with_resource(Resource::builder().with_service_name(service_name.to_owned()).build())
+        .build();
+
+    global::set_tracer_provider(provider.clone());
+
+    Ok(provider)
+}
+
+/// Flushes spans still buffered by the exporter, call it once the server has stopped.
+pub fn shutdown_tracer_provider(provider: Option<SdkTracerProvider>) {
+    if let Some(provider) = provider {
+        if let Err(e) = provider.shutdown() {
+            tracing::error!("Failed to shut down tracer provider: {:?}", e);
+        }
+    }
+}
+
+/// W3C trace context headers (`traceparent`, `tracestate`) for the current span,
+/// to be attached to requests to other services.
+pub fn trace_context_headers() -> HashMap<String, String> {
+    let context = tracing::Span::current().context();
+    let mut headers = HashMap::new();
+
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut headers));
+
+    headers
+}
+
+pub(crate) fn tracer(provider: &SdkTracerProvider, name: &str) -> SdkTracer {
+    provider.tracer(name.to_owned())
+}
\ No newline at end of file
@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use tracing::Subscriber;
+use tracing_appender::rolling::{InitError, RollingFileAppender, Rotation};
+use tracing_bunyan_formatter::BunyanFormattingLayer;
+use tracing_subscriber::{fmt::{self, MakeWriter}, registry::LookupSpan, Layer};
+
+use crate::redaction::RedactionSettings;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Bunyan,
+    Compact,
+    Pretty,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LogFileSettings {
+    pub directory: PathBuf,
+    /// Defaults to the service name
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Number of rotated files to keep, all of them if unset
+    pub max_files: Option<usize>,
+}
+
+impl LogFileSettings {
+    fn appender(&self, name: &str) -> Result<RollingFileAppender, InitError> {
+        let mut builder = RollingFileAppender::builder()
+            .rotation(self.rotation.into())
+            .filename_prefix(self.prefix.clone().unwrap_or_else(|| name.to_owned()))
+            .filename_suffix("log");
+
+        if let Some(max_files) = self.max_files {
+            builder = builder.max_log_files(max_files);
+        }
+
+        builder.build(&self.directory)
+    }
+}
+
+/// Log output of a service, e.g. `APP_LOG__FORMAT=pretty` for local work.
+/// Files are written in the same format as the console, without colors.
+/// `redaction` applies to both outputs and to exported spans.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct LogSettings {
+    #[serde(default)]
+    pub format: LogFormat,
+    pub file: Option<LogFileSettings>,
+    #[serde(default)]
+    pub redaction: RedactionSettings,
+}
+
+impl LogSettings {
+    pub(crate) fn layers<S, Sink>(&self, name: &str, sink: Sink) -> Vec<Box<dyn Layer<S> + Send + Sync>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        let mut layers = vec![format_layer(self.format, name, sink, true)];
+
+        if let Some(file) = &self.file {
+            let appender = file.appender(name).expect("Failed to create log file appender");
+            layers.push(format_layer(self.format, name, appender, false));
+        }
+
+        layers
+    }
+}
+
+fn format_layer<S, W>(format: LogFormat, name: &str, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Bunyan => Box::new(BunyanFormattingLayer::new(name.to_owned(), writer)),
+        LogFormat::Compact => Box::new(fmt::layer().compact().with_ansi(ansi).with_writer(writer)),
+        LogFormat::Pretty => Box::new(fmt::layer().pretty().with_ansi(ansi).with_writer(writer)),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,538 @@
+use std::{any::TypeId, borrow::Cow, collections::HashMap, fmt, sync::RwLock};
+
+use regex::Regex;
+use serde::Deserialize;
+use tracing::{
+    callsite::Identifier,
+    field::{display, DisplayValue, Field, FieldSet, Value, ValueSet, Visit},
+    level_filters::LevelFilter,
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+    Dispatch, Event, Metadata, Subscriber,
+};
+use tracing_core::span::Current;
+use tracing_subscriber::field::RecordFields;
+
+const PLACEHOLDER: &str = "[REDACTED]";
+
+const DEFAULT_FIELDS: &[&str] = &[
+    "password",
+    "refresh_token",
+    "access_token",
+    "code_verifier",
+    "auth_code",
+    "fingerprint",
+    "authorization",
+    "cookie",
+];
+
+/// Authorization codes in OAuth redirect URLs. Not a default field, a bare `code`
+/// would also catch status and database error codes.
+const AUTH_CODE_PATTERN: &str = r#"(?i)([?&]code=)[^&#\s"\\]+"#;
+const BEARER_PATTERN: &str = r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]+=*";
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}";
+
+/// Values scrubbed from log output. Bearer tokens, emails and the default
+/// field names are always redacted, `fields` and `patterns` extend them.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RedactionSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Names whose values are redacted, e.g. `password` in `password=...`,
+    /// `password: "..."` or `"password":"..."`. Matched case-insensitively.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Regular expressions whose matches are redacted
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            fields: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+struct Rule {
+    regex: Regex,
+    replacement: String,
+}
+
+impl Rule {
+    fn new(pattern: &str, replacement: impl Into<String>) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+            replacement: replacement.into(),
+        })
+    }
+}
+
+pub struct Redactor {
+    rules: Vec<Rule>,
+    fields: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(settings: &RedactionSettings) -> Result<Self, regex::Error> {
+        if !settings.enabled {
+            return Ok(Self {
+                rules: Vec::new(),
+                fields: Vec::new(),
+            })
+        }
+
+        let names = DEFAULT_FIELDS
+            .iter()
+            .copied()
+            .chain(settings.fields.iter().map(String::as_str))
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        let fields = names
+            .iter()
+            .map(|name| regex::escape(name))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        let mut rules = vec![
+            // Before the field rules, which would only take `Bearer` from `authorization: Bearer ...`
+            Rule::new(BEARER_PATTERN, format!("Bearer {PLACEHOLDER}"))?,
+            Rule::new(EMAIL_PATTERN, PLACEHOLDER)?,
+            Rule::new(AUTH_CODE_PATTERN, format!("${{1}}{PLACEHOLDER}"))?,
+            // Quoted values keep their quotes, so JSON output stays valid
+            Rule::new(
+                &format!(r#"(?i)((?:^|\W)(?:{fields})\\?"?\s*[:=]\s*\\?")(?:[^"\\]|\\.)*?(\\?")"#),
+                format!("${{1}}{PLACEHOLDER}${{2}}"),
+            )?,
+            Rule::new(
+                &format!(r#"(?i)((?:^|\W)(?:{fields})\s*[:=]\s*)[^\s"\\,;&}})\]]+"#),
+                format!("${{1}}{PLACEHOLDER}"),
+            )?,
+        ];
+
+        for pattern in &settings.patterns {
+            rules.push(Rule::new(pattern, PLACEHOLDER)?);
+        }
+
+        Ok(Self { rules, fields: names })
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether a structured field holds a sensitive value as a whole
+    fn is_sensitive(&self, name: &str) -> bool {
+        self.fields.iter().any(|field| field.eq_ignore_ascii_case(name))
+    }
+
+    pub fn redact<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let mut output = Cow::Borrowed(input);
+
+        for rule in &self.rules {
+            let replaced = match rule.regex.replace_all(&output, rule.replacement.as_str()) {
+                Cow::Owned(replaced) => Some(replaced),
+                Cow::Borrowed(_) => None,
+            };
+
+            if let Some(replaced) = replaced {
+                output = Cow::Owned(replaced);
+            }
+        }
+
+        output
+    }
+}
+
+/// Scrubs field values before they reach the wrapped subscriber, so every layer
+/// (log output and OTLP export alike) only ever sees the redacted values.
+/// Fields named like a sensitive field are replaced as a whole, text values go
+/// through the [`Redactor`] rules.
+pub struct RedactingSubscriber<S> {
+    inner: S,
+    redactor: Redactor,
+    /// Span records carry no metadata, their FieldSet is looked up by callsite
+    callsites: RwLock<HashMap<Identifier, &'static Metadata<'static>>>,
+}
+
+impl<S> RedactingSubscriber<S> {
+    pub fn new(inner: S, redactor: Redactor) -> Self {
+        Self {
+            inner,
+            redactor,
+            callsites: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Values of `values` with redacted replacements, `None` if nothing needs redacting
+    fn capture(&self, values: &impl RecordFields) -> Option<Vec<(Field, Captured)>> {
+        if self.redactor.is_disabled() {
+            return None
+        }
+
+        let mut capture = Capture {
+            redactor: &self.redactor,
+            values: Vec::new(),
+            redacted: false,
+        };
+        values.record(&mut capture);
+
+        // ValueSets are rebuilt from fixed size arrays, callsites never have more fields
+        (capture.redacted && capture.values.len() <= MAX_FIELDS).then_some(capture.values)
+    }
+}
+
+const MAX_FIELDS: usize = 32;
+
+/// Calls `f` with a ValueSet of `fields` holding the captured values
+fn with_value_set<R>(
+    fields: &'static FieldSet,
+    values: &[(Field, Captured)],
+    f: impl FnOnce(&ValueSet<'_>) -> R,
+) -> R {
+    // Unused slots repeat the first field without a value, which visitors skip
+    let entries: [(&Field, Option<&dyn Value>); MAX_FIELDS] = std::array::from_fn(|i| match values.get(i) {
+        Some((field, value)) => (field, Some(value.as_value())),
+        None => (&values[0].0, None),
+    });
+
+    f(&fields.value_set(&entries))
+}
+
+enum Captured {
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    F64(f64),
+    Bool(bool),
+    Str(String),
+    Debug(DisplayValue<String>),
+}
+
+impl Captured {
+    fn as_value(&self) -> &dyn Value {
+        match self {
+            Captured::I64(value) => value,
+            Captured::U64(value) => value,
+            Captured::I128(value) => value,
+            Captured::U128(value) => value,
+            Captured::F64(value) => value,
+            Captured::Bool(value) => value,
+            Captured::Str(value) => value,
+            Captured::Debug(value) => value,
+        }
+    }
+}
+
+struct Capture<'r> {
+    redactor: &'r Redactor,
+    values: Vec<(Field, Captured)>,
+    redacted: bool,
+}
+
+impl Capture<'_> {
+    fn push(&mut self, field: &Field, value: Captured) {
+        if self.redactor.is_sensitive(field.name()) {
+            self.redacted = true;
+            self.values.push((field.clone(), Captured::Str(PLACEHOLDER.to_owned())));
+        } else {
+            self.values.push((field.clone(), value));
+        }
+    }
+
+    fn push_text(&mut self, field: &Field, text: &str, value: impl FnOnce(String) -> Captured) {
+        let redacted = match self.redactor.redact(text) {
+            Cow::Owned(redacted) => {
+                self.redacted = true;
+                redacted
+            }
+            Cow::Borrowed(text) => text.to_owned(),
+        };
+
+        self.push(field, value(redacted));
+    }
+}
+
+impl Visit for Capture<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, Captured::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, Captured::U64(value));
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.push(field, Captured::I128(value));
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.push(field, Captured::U128(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, Captured::F64(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, Captured::Bool(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push_text(field, value, Captured::Str);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push_text(field, &format!("{value:?}"), |text| Captured::Debug(display(text)));
+    }
+}
+
+impl<S: Subscriber> Subscriber for RedactingSubscriber<S> {
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.is_span() {
+            self.callsites
+                .write()
+                .unwrap()
+                .insert(metadata.callsite(), metadata);
+        }
+
+        self.inner.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let Some(values) = self.capture(span) else {
+            return self.inner.new_span(span)
+        };
+
+        let metadata = span.metadata();
+        with_value_set(metadata.fields(), &values, |values| {
+            let attributes = if span.is_root() {
+                Attributes::new_root(metadata, values)
+            } else if span.is_contextual() {
+                Attributes::new(metadata, values)
+            } else {
+                Attributes::child_of(span.parent().cloned().expect("explicit parent"), metadata, values)
+            };
+
+            self.inner.new_span(&attributes)
+        })
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let Some(captured) = self.capture(values) else {
+            return self.inner.record(span, values)
+        };
+
+        let metadata = self
+            .callsites
+            .read()
+            .unwrap()
+            .get(&captured[0].0.callsite())
+            .copied();
+
+        // Every callsite is registered before its spans exist, dropping beats leaking
+        if let Some(metadata) = metadata {
+            with_value_set(metadata.fields(), &captured, |values| {
+                self.inner.record(span, &Record::new(values))
+            });
+        }
+    }
+
+    fn record_follows_from(&self, span: &Id, follows: &Id) {
+        self.inner.record_follows_from(span, follows);
+    }
+
+    fn event_enabled(&self, event: &Event<'_>) -> bool {
+        self.inner.event_enabled(event)
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        let Some(values) = self.capture(event) else {
+            return self.inner.event(event)
+        };
+
+        let metadata = event.metadata();
+        with_value_set(metadata.fields(), &values, |values| {
+            if event.is_contextual() {
+                self.inner.event(&Event::new(metadata, values));
+            } else {
+                self.inner.event(&Event::new_child_of(event.parent().cloned(), metadata, values));
+            }
+        });
+    }
+
+    fn enter(&self, span: &Id) {
+        self.inner.enter(span);
+    }
+
+    fn exit(&self, span: &Id) {
+        self.inner.exit(span);
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        self.inner.clone_span(id)
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        self.inner.try_close(id)
+    }
+
+    fn current_span(&self) -> Current {
+        self.inner.current_span()
+    }
+
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            return Some(self as *const Self as *const ())
+        }
+
+        // Lets the OpenTelemetry layer and the registry be found through the wrapper
+        unsafe { self.inner.downcast_raw(id) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{field::Empty, info, info_span, span};
+    use tracing_subscriber::{layer::{Context, SubscriberExt}, Layer, Registry};
+
+    use super::*;
+
+    /// Collects every field value as `name=value`, like an exporting layer would see them
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Collect {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.lock().unwrap().push(format!("{}={}", field.name(), value));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.lock().unwrap().push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for Collect {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _: &span::Id, _: Context<'_, S>) {
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(&self, _: &span::Id, values: &Record<'_>, _: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+
+        fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+            event.record(&mut self.clone());
+        }
+    }
+
+    fn collect(settings: &RedactionSettings, f: impl FnOnce()) -> Vec<String> {
+        let collect = Collect::default();
+        let subscriber = RedactingSubscriber::new(
+            Registry::default().with(collect.clone()),
+            Redactor::new(settings).unwrap(),
+        );
+
+        tracing::subscriber::with_default(subscriber, f);
+
+        let values = collect.0.lock().unwrap().clone();
+        values
+    }
+
+    #[test]
+    fn redacts_sensitive_fields_and_keeps_the_rest() {
+        let values = collect(&RedactionSettings::default(), || {
+            info!(password = "hunter2", user = "bob", status = 200, "signed in as {}", "bob@example.com");
+        });
+
+        assert!(values.contains(&"password=[REDACTED]".to_owned()), "{values:?}");
+        assert!(values.contains(&"user=bob".to_owned()), "{values:?}");
+        assert!(values.contains(&"status=200".to_owned()), "{values:?}");
+        assert!(values.contains(&"message=signed in as [REDACTED]".to_owned()), "{values:?}");
+    }
+
+    #[test]
+    fn redacts_span_attributes_and_records() {
+        let values = collect(&RedactionSettings::default(), || {
+            let span = info_span!(
+                "request",
+                http.target = "/login?password=secret&next=/home",
+                authorization = Empty,
+            );
+            span.record("authorization", "Bearer abc.def");
+        });
+
+        assert_eq!(
+            values,
+            vec![
+                "http.target=/login?password=[REDACTED]&next=/home".to_owned(),
+                "authorization=[REDACTED]".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn redacts_authorization_codes_but_not_other_codes() {
+        let values = collect(&RedactionSettings::default(), || {
+            info!(http.target = "/callback?code=abc123&state=xyz", "redirected");
+            info!(code = "23505", "database error");
+            info!("upstream returned status code: 500");
+        });
+
+        assert_eq!(
+            values,
+            vec![
+                "message=redirected".to_owned(),
+                "http.target=/callback?code=[REDACTED]&state=xyz".to_owned(),
+                "message=database error".to_owned(),
+                "code=23505".to_owned(),
+                "message=upstream returned status code: 500".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_configured_fields_and_patterns() {
+        let settings = RedactionSettings {
+            fields: vec!["ssn".to_owned()],
+            patterns: vec![r"\d{4}-\d{4}".to_owned()],
+            ..Default::default()
+        };
+        let values = collect(&settings, || info!(ssn = 123, card = "1234-5678"));
+
+        assert_eq!(values, vec!["ssn=[REDACTED]".to_owned(), "card=[REDACTED]".to_owned()]);
+    }
+
+    #[test]
+    fn passes_values_through_when_disabled() {
+        let settings = RedactionSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        let values = collect(&settings, || info!(password = "hunter2"));
+
+        assert_eq!(values, vec!["password=hunter2".to_owned()]);
+    }
+}
\ No newline at end of file
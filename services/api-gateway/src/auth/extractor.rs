@@ -1,8 +1,7 @@
 use std::future::{ready, Ready};
 
 use actix_web::{FromRequest, HttpMessage, HttpRequest};
-
-use super::jwt::Claims;
+use auth_client::jwt::Claims;
 
 pub struct UserId(pub Option<i32>);
 
@@ -1,25 +1,45 @@
 use std::{future::{ready, Ready}, rc::Rc};
 
 use actix_web::{body::{EitherBody, MessageBody}, dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, http::{header::AUTHORIZATION, StatusCode}, web::Data, Error, HttpMessage, HttpResponse};
+use auth_client::jwt::{Claims, JwtValidator};
 use futures_util::future::LocalBoxFuture;
 
-use super::jwt::JwtValidator;
+/// Whether a token needs one or every one of the required roles or scopes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchMode {
+    Any,
+    All,
+}
+
+/// What to do with a token whose revocation status can't be checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevocationFallback {
+    /// Revoked tokens still expire on their own
+    Allow,
+    Reject,
+}
 
 #[derive(Clone)]
 pub struct JwtConfig {
     pub required_roles: Option<Vec<String>>,
+    pub roles_mode: MatchMode,
     pub required_scopes: Option<Vec<String>>,
+    pub scopes_mode: MatchMode,
     pub require_admin: bool,
     pub optional: bool,
+    pub revocation_fallback: Option<RevocationFallback>,
 }
 
 impl Default for JwtConfig {
     fn default() -> Self {
         Self {
             required_roles: None,
+            roles_mode: MatchMode::Any,
             required_scopes: None,
+            scopes_mode: MatchMode::All,
             require_admin: false,
             optional: false,
+            revocation_fallback: None,
         }
     }
 }
@@ -29,13 +49,29 @@ impl JwtConfig {
         Self::default()
     }
 
+    /// Requires any of the given roles.
     pub fn require_roles(mut self, roles: Vec<&str>) -> Self {
         self.required_roles = Some(roles.into_iter().map(|s| s.to_string()).collect());
+        self.roles_mode = MatchMode::Any;
+        self
+    }
+
+    pub fn require_all_roles(mut self, roles: Vec<&str>) -> Self {
+        self.required_roles = Some(roles.into_iter().map(|s| s.to_string()).collect());
+        self.roles_mode = MatchMode::All;
         self
     }
 
+    /// Requires all of the given scopes.
     pub fn require_scopes(mut self, scopes: Vec<&str>) -> Self {
         self.required_scopes = Some(scopes.into_iter().map(|s| s.to_string()).collect());
+        self.scopes_mode = MatchMode::All;
+        self
+    }
+
+    pub fn require_any_scope(mut self, scopes: Vec<&str>) -> Self {
+        self.required_scopes = Some(scopes.into_iter().map(|s| s.to_string()).collect());
+        self.scopes_mode = MatchMode::Any;
         self
     }
 
@@ -48,6 +84,23 @@ impl JwtConfig {
         self.optional = true;
         self
     }
+
+    pub fn on_revocation_error(mut self, fallback: RevocationFallback) -> Self {
+        self.revocation_fallback = Some(fallback);
+        self
+    }
+
+    /// Unless configured, only routes that require nothing beyond a valid token
+    /// let through tokens that can't be checked.
+    fn revocation_fallback(&self) -> RevocationFallback {
+        let restricted = self.required_roles.is_some() || self.required_scopes.is_some() || self.require_admin;
+
+        match self.revocation_fallback {
+            Some(fallback) => fallback,
+            None if restricted => RevocationFallback::Reject,
+            None => RevocationFallback::Allow,
+        }
+    }
 }
 
 pub struct JwtMiddleware {
@@ -114,55 +167,43 @@ where
                 }
             };
 
-            let auth_header = req
-                .headers()
-                .get(AUTHORIZATION)
-                .and_then(|h| h.to_str().ok());
+            // An outer middleware may have authenticated the request already
+            let authenticated = req.extensions().contains::<Claims>();
 
-            let auth_header = match auth_header {
-                Some(header) => header,
-                None => {
-                    if config.optional {
-                        let res = service.call(req).await?;
-                        return Ok(res.map_body(|_, body| EitherBody::left(body)));
-                    } else {
-                        return Ok(create_error_response(req, "Missing Authorization header", StatusCode::UNAUTHORIZED));
-                    }
-                }
+            let result = if authenticated {
+                Ok(())
+            } else {
+                authenticate(&req, &validator)
+                    .await
+                    .map(|claims| { req.extensions_mut().insert(claims); })
+                    .map_err(|message| (message, StatusCode::UNAUTHORIZED))
             };
 
-            if !auth_header.starts_with("Bearer ") {
-                return Ok(create_error_response(req, "Invalid Authorization header format", StatusCode::UNAUTHORIZED));
-            }
-        
-            let token = auth_header.trim_start_matches("Bearer ");
-            
-            if token.is_empty() {
-                return Ok(create_error_response(req, "Empty token", StatusCode::UNAUTHORIZED));
-            }
-
-            let claims = match validator.validate_token(&token).await {
-                Ok(claims) => claims,
-                Err(e) => {
-                    tracing::warn!("JWT validation failed: {}", e);
-                    return Ok(create_error_response(req, &e.to_string(), StatusCode::UNAUTHORIZED));
-                }
+            let result = match result {
+                Ok(()) => check_revocation(&req, &validator, config.revocation_fallback()).await,
+                Err(e) => Err(e),
             };
 
-            if let Some(required_roles) = &config.required_roles {
-                let role_refs: Vec<&str> = required_roles.iter().map(|s| s.as_str()).collect();
-                if !claims.has_any_role(&role_refs) {
-                    tracing::warn!("User {} lacks required roles: {:?}", claims.sub, required_roles);
-                    return Ok(create_error_response(req, "Insufficient permissions", StatusCode::FORBIDDEN));
+            if let Err((message, status)) = result {
+                if !config.optional {
+                    return Ok(create_error_response(req, &message, status));
                 }
-            }
 
-            if config.require_admin && !claims.is_admin() {
-                tracing::warn!("User {} is not an admin but admin access required", claims.sub);
-                return Ok(create_error_response(req, "Admin access required", StatusCode::FORBIDDEN));
+                // Public routes keep working with an expired, revoked or otherwise bad token
+                tracing::debug!("Continuing anonymously: {}", message);
+                req.extensions_mut().remove::<Claims>();
+                let res = service.call(req).await?;
+                return Ok(res.map_body(|_, body| EitherBody::left(body)));
             }
 
-            req.extensions_mut().insert(claims);
+            let denied = match req.extensions().get::<Claims>() {
+                Some(claims) => authorize(claims, &config),
+                None => Some("Missing Authorization header"),
+            };
+
+            if let Some(message) = denied {
+                return Ok(create_error_response(req, message, StatusCode::FORBIDDEN));
+            }
 
             let res = service.call(req).await?;
             Ok(res.map_body(|_, body| EitherBody::left(body)))
@@ -170,6 +211,109 @@ where
     }
 }
 
+/// Set once the token of the request is known not to be revoked.
+struct RevocationChecked;
+
+/// Validates the bearer token of the request.
+async fn authenticate(req: &ServiceRequest, validator: &JwtValidator) -> Result<Claims, String> {
+    let auth_header = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .ok_or("Missing Authorization header")?;
+
+    let token = auth_header
+        .strip_prefix("Bearer ")
+        .ok_or("Invalid Authorization header format")?;
+
+    if token.is_empty() {
+        return Err("Empty token".to_string());
+    }
+
+    validator.validate_token(token).await.map_err(|e| {
+        tracing::warn!("JWT validation failed: {}", e);
+        e.to_string()
+    })
+}
+
+/// Makes sure the token of the request hasn't been revoked. A token an outer middleware
+/// let through without checking is checked again, so that stricter routes can reject it.
+async fn check_revocation(
+    req: &ServiceRequest,
+    validator: &JwtValidator,
+    fallback: RevocationFallback,
+) -> Result<(), (String, StatusCode)> {
+    let (jti, sub) = {
+        let extensions = req.extensions();
+
+        match extensions.get::<Claims>() {
+            Some(claims) if !extensions.contains::<RevocationChecked>() => (claims.jti.clone(), claims.sub.clone()),
+            _ => return Ok(()),
+        }
+    };
+
+    match validator.is_revoked(&jti).await {
+        Ok(false) => {
+            req.extensions_mut().insert(RevocationChecked);
+            Ok(())
+        },
+        Ok(true) => {
+            tracing::warn!("Rejected revoked token {} of user {}", jti, sub);
+            Err(("Token has been revoked".to_string(), StatusCode::UNAUTHORIZED))
+        },
+        Err(e) => {
+            tracing::error!("Failed to check token revocation: {:?}", e);
+
+            match fallback {
+                RevocationFallback::Allow => Ok(()),
+                RevocationFallback::Reject => Err(("Token revocation could not be checked".to_string(), StatusCode::SERVICE_UNAVAILABLE)),
+            }
+        },
+    }
+}
+
+/// Returns why the claims don't satisfy the configured requirements, if they don't.
+fn authorize(claims: &Claims, config: &JwtConfig) -> Option<&'static str> {
+    if let Some(required_roles) = &config.required_roles {
+        if !has_roles(claims, required_roles, config.roles_mode) {
+            tracing::warn!("User {} lacks required roles: {:?}", claims.sub, required_roles);
+            return Some("Insufficient permissions");
+        }
+    }
+
+    if let Some(required_scopes) = &config.required_scopes {
+        if !has_scopes(claims, required_scopes, config.scopes_mode) {
+            tracing::warn!("Token of user {} lacks required scopes: {:?}", claims.sub, required_scopes);
+            return Some("Insufficient scope");
+        }
+    }
+
+    if config.require_admin && !claims.is_admin() {
+        tracing::warn!("User {} is not an admin but admin access required", claims.sub);
+        return Some("Admin access required");
+    }
+
+    None
+}
+
+fn has_roles(claims: &Claims, required: &[String], mode: MatchMode) -> bool {
+    let roles: Vec<&str> = required.iter().map(|s| s.as_str()).collect();
+
+    match mode {
+        MatchMode::Any => claims.has_any_role(&roles),
+        MatchMode::All => claims.has_all_roles(&roles),
+    }
+}
+
+fn has_scopes(claims: &Claims, required: &[String], mode: MatchMode) -> bool {
+    let scopes: Vec<&str> = required.iter().map(|s| s.as_str()).collect();
+
+    match mode {
+        MatchMode::Any => claims.has_any_scope(&scopes),
+        MatchMode::All => claims.has_all_scopes(&scopes),
+    }
+}
+
 fn create_error_response<B>(req: ServiceRequest, message: &str, status: StatusCode) -> ServiceResponse<EitherBody<B>>
 where
     B: MessageBody
@@ -194,11 +338,182 @@ impl JwtMiddleware {
         Self::new(JwtConfig::new().require_roles(roles))
     }
 
+    pub fn require_all_roles(roles: Vec<&str>) -> Self {
+        Self::new(JwtConfig::new().require_all_roles(roles))
+    }
+
     pub fn require_scopes(scopes: Vec<&str>) -> Self {
         Self::new(JwtConfig::new().require_scopes(scopes))
     }
 
+    pub fn require_any_scope(scopes: Vec<&str>) -> Self {
+        Self::new(JwtConfig::new().require_any_scope(scopes))
+    }
+
     pub fn optional() -> Self {
         Self::new(JwtConfig::new().optional())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{dev::Service as _, http::{header::AUTHORIZATION, StatusCode}, test::{call_service, init_service, read_body, TestRequest}, web, App, HttpMessage, HttpRequest};
+    use auth_client::jwt::{Claims, JwtValidator};
+
+    use super::*;
+
+    fn claims(roles: &[&str], scope: &str) -> Claims {
+        Claims {
+            sub: "1".to_string(),
+            iat: 0,
+            exp: i64::MAX,
+            iss: "auth-service".to_string(),
+            jti: "jti".to_string(),
+            scope: scope.to_string(),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+        }
+    }
+
+    /// Calls a route behind `config`. With `roles`, an outer middleware has already
+    /// authenticated the request. Auth-service is unreachable, so revocations can't be checked.
+    async fn call(config: JwtConfig, roles: Option<&'static [&'static str]>, authorization: Option<&str>) -> (StatusCode, String) {
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(JwtValidator::new("http://127.0.0.1:9".to_string())))
+                .route("/", web::get().to(|req: HttpRequest| async move {
+                    if req.extensions().contains::<Claims>() { "user" } else { "anonymous" }
+                }))
+                .wrap(JwtMiddleware::new(config))
+                .wrap_fn(move |req, srv| {
+                    if let Some(roles) = roles {
+                        req.extensions_mut().insert(claims(roles, "ratings:write"));
+                    }
+                    srv.call(req)
+                })
+        ).await;
+
+        let mut req = TestRequest::get();
+        if let Some(authorization) = authorization {
+            req = req.insert_header((AUTHORIZATION, authorization));
+        }
+
+        let res = call_service(&app, req.to_request()).await;
+        let status = res.status();
+
+        (status, String::from_utf8(read_body(res).await.to_vec()).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn unchecked_tokens_pass_routes_without_requirements() {
+        assert_eq!(call(JwtConfig::new(), Some(&[]), None).await, (StatusCode::OK, "user".to_string()));
+    }
+
+    #[actix_web::test]
+    async fn unchecked_tokens_are_rejected_by_restricted_routes() {
+        for config in [
+            JwtConfig::new().require_scopes(vec!["ratings:write"]),
+            JwtConfig::new().require_roles(vec!["admin"]),
+            JwtConfig::new().require_admin(),
+        ] {
+            assert_eq!(call(config, Some(&["admin"]), None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+        }
+    }
+
+    #[actix_web::test]
+    async fn revocation_fallback_can_be_configured() {
+        let config = JwtConfig::new()
+            .require_scopes(vec!["ratings:write"])
+            .on_revocation_error(RevocationFallback::Allow);
+        assert_eq!(call(config, Some(&[]), None).await.0, StatusCode::OK);
+
+        let config = JwtConfig::new().on_revocation_error(RevocationFallback::Reject);
+        assert_eq!(call(config, Some(&[]), None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_web::test]
+    async fn optional_routes_drop_unchecked_tokens_they_reject() {
+        let config = JwtConfig::new()
+            .optional()
+            .on_revocation_error(RevocationFallback::Reject);
+
+        assert_eq!(call(config, Some(&[]), None).await, (StatusCode::OK, "anonymous".to_string()));
+    }
+
+    #[test]
+    fn any_mode_needs_one_of_the_roles() {
+        let required = vec!["admin".to_string(), "librarian".to_string()];
+
+        assert!(has_roles(&claims(&["librarian"], ""), &required, MatchMode::Any));
+        assert!(!has_roles(&claims(&["user"], ""), &required, MatchMode::Any));
+        assert!(!has_roles(&claims(&[], ""), &required, MatchMode::Any));
+    }
+
+    #[test]
+    fn all_mode_needs_every_role() {
+        let required = vec!["admin".to_string(), "librarian".to_string()];
+
+        assert!(has_roles(&claims(&["librarian", "user", "admin"], ""), &required, MatchMode::All));
+        assert!(!has_roles(&claims(&["librarian"], ""), &required, MatchMode::All));
+    }
+
+    #[test]
+    fn scopes_match_whole_words_in_either_mode() {
+        let required = vec!["ratings:read".to_string(), "ratings:write".to_string()];
+        let token = claims(&[], "books:read ratings:write");
+
+        assert!(has_scopes(&token, &required, MatchMode::Any));
+        assert!(!has_scopes(&token, &required, MatchMode::All));
+        assert!(has_scopes(&claims(&[], "ratings:write ratings:read"), &required, MatchMode::All));
+        assert!(!has_scopes(&claims(&[], "ratings"), &required, MatchMode::Any));
+    }
+
+    #[test]
+    fn tokens_without_scopes_fail_scope_requirements() {
+        for scope in ["", "   "] {
+            let token = claims(&["admin"], scope);
+
+            assert_eq!(authorize(&token, &JwtConfig::new().require_scopes(vec!["ratings:write"])), Some("Insufficient scope"));
+            assert_eq!(authorize(&token, &JwtConfig::new().require_any_scope(vec!["ratings:write"])), Some("Insufficient scope"));
+            assert_eq!(authorize(&token, &JwtConfig::new()), None);
+        }
+    }
+
+    #[test]
+    fn authorize_reports_the_first_unmet_requirement() {
+        let config = JwtConfig::new()
+            .require_roles(vec!["librarian"])
+            .require_scopes(vec!["ratings:write"])
+            .require_admin();
+
+        assert_eq!(authorize(&claims(&["user"], "ratings:write"), &config), Some("Insufficient permissions"));
+        assert_eq!(authorize(&claims(&["librarian"], ""), &config), Some("Insufficient scope"));
+        assert_eq!(authorize(&claims(&["librarian"], "ratings:write"), &config), Some("Admin access required"));
+        assert_eq!(authorize(&claims(&["librarian", "admin"], "ratings:write"), &config), None);
+    }
+
+    #[actix_web::test]
+    async fn requirements_are_enforced_on_requests() {
+        let config = || JwtConfig::new().on_revocation_error(RevocationFallback::Allow);
+
+        assert_eq!(call(config().require_any_scope(vec!["books:write", "ratings:write"]), Some(&[]), None).await.0, StatusCode::OK);
+        assert_eq!(call(config().require_scopes(vec!["books:write", "ratings:write"]), Some(&[]), None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(call(config().require_all_roles(vec!["admin", "librarian"]), Some(&["admin"]), None).await.0, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn optional_routes_continue_anonymously_with_bad_tokens() {
+        for authorization in [None, Some("Bearer garbage"), Some("Basic dXNlcjpwYXNz"), Some("Bearer ")] {
+            assert_eq!(
+                call(JwtConfig::new().optional(), None, authorization).await,
+                (StatusCode::OK, "anonymous".to_string())
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn required_routes_reject_bad_tokens() {
+        for authorization in [None, Some("Bearer garbage"), Some("Basic dXNlcjpwYXNz")] {
+            assert_eq!(call(JwtConfig::new(), None, authorization).await.0, StatusCode::UNAUTHORIZED);
+        }
+    }
 }
\ No newline at end of file
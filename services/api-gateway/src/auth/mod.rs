@@ -1,3 +1,2 @@
-pub mod jwt;
 pub mod middleware;
 pub mod extractor;
\ No newline at end of file
@@ -1,17 +1,76 @@
-use std::{collections::HashMap, str::FromStr, time::Duration};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 
-use actix_web::{dev::PeerAddr, error, web, Error, HttpRequest, HttpResponse};
+use actix_web::{dev::PeerAddr, error, http::header::{self, HeaderValue}, web, Error, HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError};
 use futures_util::StreamExt as _;
-use reqwest::{redirect::Policy, Client, Url};
+use reqwest::{redirect::Policy, Client, IntoUrl, RequestBuilder, Url};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
-use crate::{config::ServicesSettings, error::ApiError, schema::{Author, BookFullSchema, BookRatingSchema, BookSchema, BulkGetSchema, ChapterFullSchema, ConstantsSchema, GetListSchema, InputChapterSchema, PaginationSchema, RateInputSchema, RateOutputSchema, SearchQuery, UserIdSchema}};
+use crate::{config::ServicesSettings, error::ApiError, upstream::Upstream, schema::{Author, BookFullSchema, BookRatingSchema, BookSchema, BulkGetSchema, ChapterFullSchema, ConstantsSchema, GetListSchema, InputChapterSchema, PaginationSchema, RateInputSchema, RateOutputSchema, SearchQuery, UserIdSchema}};
+
+/// `Cache-Control` and `Vary` of upstream responses, passed on to the caller
+/// so that the gateway cache honours them.
+#[derive(Debug, Default)]
+pub struct CacheHeaders {
+    cache_control: Vec<HeaderValue>,
+    vary: Vec<HeaderValue>,
+}
+
+impl CacheHeaders {
+    fn from_response(response: &reqwest::Response) -> Self {
+        let values = |name: &str| {
+            response.headers()
+                .get_all(name)
+                .iter()
+                .filter_map(|value| HeaderValue::from_bytes(value.as_bytes()).ok())
+                .collect()
+        };
+
+        Self {
+            cache_control: values("cache-control"),
+            vary: values("vary"),
+        }
+    }
+
+    /// Combines the headers of responses merged into a single body. The cache keeps
+    /// the most restrictive `Cache-Control` directives and the union of `Vary`.
+    pub fn merge(mut self, other: Self) -> Self {
+        self.cache_control.extend(other.cache_control);
+        self.vary.extend(other.vary);
+        self
+    }
+
+    pub fn apply<'a>(&self, res: &'a mut HttpResponseBuilder) -> &'a mut HttpResponseBuilder {
+        for value in &self.cache_control {
+            res.append_header((header::CACHE_CONTROL, value.clone()));
+        }
+
+        for value in &self.vary {
+            res.append_header((header::VARY, value.clone()));
+        }
+
+        res
+    }
+}
+
+/// Reports the outcome of a request that is not retried to the upstream's circuit breaker.
+fn record_outcome(upstream: &Upstream, result: &reqwest::Result<reqwest::Response>) {
+    match result {
+        Ok(response) if !response.status().is_server_error() => upstream.breaker.record_success(),
+        _ => upstream.breaker.record_failure(),
+    }
+}
+
+/// Deserialized upstream response along with its [`CacheHeaders`].
+pub struct Upstreamed<T> {
+    pub body: T,
+    pub cache_headers: CacheHeaders,
+}
 
 pub struct ServiceClient {
     client: Client,
-    config: ServicesSettings,
-    // cache maybe
+    book_catalog: Arc<Upstream>,
+    ratings: Arc<Upstream>,
 }
 
 impl ServiceClient {
@@ -26,7 +85,15 @@ impl ServiceClient {
 
         Self {
             client,
-            config: settings,
+            book_catalog: Arc::new(Upstream::new(settings.book_catalog)),
+            ratings: Arc::new(Upstream::new(settings.ratings)),
+        }
+    }
+
+    /// Starts polling the instances of every upstream. Must be called within a Tokio runtime.
+    pub fn spawn_health_checks(&self) {
+        for upstream in [&self.book_catalog, &self.ratings] {
+            upstream.clone().spawn_health_checks(self.client.clone());
         }
     }
 
@@ -38,15 +105,16 @@ impl ServiceClient {
                 return Err(ApiError::ValidationError)
             },
         };
-        let url = format!("{}/api/v1/books?{}", self.config.book_catalog.url, query_string);
+        let path = format!("/api/v1/books?{}", query_string);
         let mut result: PaginationSchema<BookSchema> = match self.make_request(
-            &url,
-            &self.config.book_catalog.name,
+            &path,
+            &self.book_catalog,
             reqwest::Method::GET,
             None::<&()>,
             None::<&()>
         ).await {
             Ok(result) => result,
+            Err(e @ ApiError::ServiceUnavailable(_)) => return Err(e),
             Err(e) => {
                 tracing::error!("Failed to get books list: {:?}", e);
                 return Err(ApiError::ServiceError("Failed to get books list".to_string()))
@@ -62,11 +130,11 @@ impl ServiceClient {
             .map(|item| item.id)
             .collect::<Vec<_>>();
 
-        let rating_url = format!("{}/ratings/bulk_get", self.config.ratings.url);
+        let rating_path = "/ratings/bulk_get";
 
         let ratings_result: Result<Vec<BookRatingSchema>, ApiError> = self.make_request(
-            &rating_url,
-            &self.config.ratings.name,
+            rating_path,
+            &self.ratings,
             reqwest::Method::POST,
             None::<&()>,
             Some(&BulkGetSchema{ids})
@@ -89,42 +157,46 @@ impl ServiceClient {
         Ok(result)
     }
     
-    pub async fn get_book(&self, id: u64, user_id: Option<i32>) -> Result<BookFullSchema, ApiError> {
-        let book_url = format!("{}/api/v1/books/{}", self.config.book_catalog.url, id);
-        let rating_url = format!("{}/ratings/{}", self.config.ratings.url, id);
+    pub async fn get_book(&self, id: u64, user_id: Option<i32>) -> Result<Upstreamed<BookFullSchema>, ApiError> {
+        let book_path = format!("/api/v1/books/{}", id);
+        let rating_path = format!("/ratings/{}", id);
 
         let user_id_schema = UserIdSchema {
             user_id,
         };
 
         let (book_result, rating_result) = tokio::join!(
-            self.make_request(
-                &book_url,
-                &self.config.book_catalog.name,
+            self.fetch(
+                &book_path,
+                &self.book_catalog,
                 reqwest::Method::GET,
                 None::<&()>,
                 None::<&()>
             ),
-            self.make_request(
-                &rating_url,
-                &self.config.ratings.name,
+            self.fetch(
+                &rating_path,
+                &self.ratings,
                 reqwest::Method::POST,
                 None::<&()>,
                 Some(&user_id_schema)
             )
         );
     
-        let mut book: BookFullSchema = book_result.map_err(|e| {
-            tracing::error!("Failed to get book: {:?}", e);
-            ApiError::ServiceError("Failed to get book".to_string())
+        let mut book: Upstreamed<BookFullSchema> = book_result.map_err(|e| match e {
+            ApiError::ServiceUnavailable(_) => e,
+            e => {
+                tracing::error!("Failed to get book: {:?}", e);
+                ApiError::ServiceError("Failed to get book".to_string())
+            },
         })?;
     
-        book.rating = rating_result
-            .map_err(|e| {
-                tracing::error!("Failed to get rating: {:?}", e);
-                e
-            })
-            .ok();
+        match rating_result {
+            Ok(rating) => {
+                book.body.rating = Some(rating.body);
+                book.cache_headers = book.cache_headers.merge(rating.cache_headers);
+            },
+            Err(e) => tracing::error!("Failed to get rating: {:?}", e),
+        };
 
         Ok(book)
     }
@@ -135,14 +207,15 @@ impl ServiceClient {
         payload: web::Payload,
         peer_addr: Option<PeerAddr>
     ) -> Result<HttpResponse, Error> {
-        let url = format!("{}/api/v1{}", self.config.book_catalog.url, req.uri().path());
+        let path = format!("/api/v1{}", req.uri().path());
 
         self.forward_request(
             req,
             payload,
             actix_web::http::Method::PUT,
             peer_addr,
-            &url
+            &self.book_catalog,
+            &path
         ).await
     }
 
@@ -152,14 +225,37 @@ impl ServiceClient {
         payload: web::Payload,
         peer_addr: Option<PeerAddr>
     ) -> Result<HttpResponse, Error> {
-        let url = format!("{}/api/v1{}", self.config.book_catalog.url, req.uri().path());
+        let path = format!("/api/v1{}", req.uri().path());
 
         self.forward_request(
             req,
             payload,
             actix_web::http::Method::POST,
             peer_addr,
-            &url
+            &self.book_catalog,
+            &path
+        ).await
+    }
+
+    pub async fn forward_cache_admin(
+        &self,
+        req: HttpRequest,
+        payload: web::Payload,
+        peer_addr: Option<PeerAddr>
+    ) -> Result<HttpResponse, Error> {
+        let path = format!(
+            "/api/v1/cache/{}",
+            req.match_info().get("tail").unwrap_or_default()
+        );
+        let method = req.method().clone();
+
+        self.forward_request(
+            req,
+            payload,
+            method,
+            peer_addr,
+            &self.book_catalog,
+            &path
         ).await
     }
 
@@ -167,16 +263,16 @@ impl ServiceClient {
         &self,
         req: HttpRequest
     ) -> Result<(), ApiError> {
-        let url = format!("{}/api/v1{}", self.config.book_catalog.url, req.uri().path());
-        self.make_request(&url, &self.config.book_catalog.name, reqwest::Method::DELETE, None::<&()>, None::<&()>).await
+        let path = format!("/api/v1{}", req.uri().path());
+        self.make_request(&path, &self.book_catalog, reqwest::Method::DELETE, None::<&()>, None::<&()>).await
     }
 
-    pub async fn search<T>(&self, q: SearchQuery, entity: &str) -> Result<Vec<T>, ApiError>
+    pub async fn search<T>(&self, q: SearchQuery, entity: &str) -> Result<Upstreamed<Vec<T>>, ApiError>
     where
         T: for<'de> serde::Deserialize<'de>,
     {
-        let url = format!("{}/api/v1/search/{}", self.config.book_catalog.url, entity);
-        self.make_request(&url, &self.config.book_catalog.name, reqwest::Method::GET, Some(&q), None::<&()>).await
+        let path = format!("/api/v1/search/{}", entity);
+        self.fetch(&path, &self.book_catalog, reqwest::Method::GET, Some(&q), None::<&()>).await
     }
 
     async fn forward_request(
@@ -185,9 +281,17 @@ impl ServiceClient {
         mut payload: web::Payload,
         method: actix_web::http::Method,
         peer_addr: Option<PeerAddr>,
-        url: &str
+        upstream: &Upstream,
+        path: &str
     ) -> Result<HttpResponse, Error> {
-        let mut new_url = match Url::from_str(&url) {
+        if !upstream.breaker.try_acquire() {
+            tracing::warn!("Circuit breaker for {} is open, rejecting {} {}", upstream.name(), method, path);
+            return Ok(ApiError::ServiceUnavailable(upstream.name().to_owned()).error_response())
+        }
+
+        let endpoint = upstream.select();
+
+        let mut new_url = match Url::from_str(&format!("{}{}", endpoint.url(), path)) {
             Ok(url) => url,
             Err(e) => {
                 tracing::error!("Failed to create url from str: {:?}", e);
@@ -205,14 +309,16 @@ impl ServiceClient {
             }
         });
     
-        let mut forwarded_req = self.client
+        let mut forwarded_req = self
             .request(
                 reqwest::Method::from_bytes(method.as_str().as_bytes()).unwrap(),
                 new_url,
             )
+            .timeout(upstream.policy().timeout())
             .body(reqwest::Body::wrap_stream(UnboundedReceiverStream::new(rx)));
 
-        for (name, value) in req.headers().iter() {
+        // The client's trace context is replaced with the gateway's own span
+        for (name, value) in req.headers().iter().filter(|(h, _)| *h != "traceparent" && *h != "tracestate") {
             forwarded_req = forwarded_req.header(name.as_str(), value.as_bytes());
         }
 
@@ -221,10 +327,11 @@ impl ServiceClient {
             None => forwarded_req,
         };
     
-        let res = forwarded_req
-            .send()
-            .await
-            .map_err(error::ErrorInternalServerError)?;
+        // The body has been consumed, so the request can't be retried
+        let result = forwarded_req.send().await;
+        record_outcome(upstream, &result);
+
+        let res = result.map_err(error::ErrorInternalServerError)?;
 
             let mut client_resp =
             HttpResponse::build(actix_web::http::StatusCode::from_u16(res.status().as_u16()).unwrap());
@@ -239,31 +346,38 @@ impl ServiceClient {
         Ok(client_resp.streaming(res.bytes_stream()))
     }
 
-    pub async fn get_constants(&self) -> Result<ConstantsSchema, ApiError> {
-        let url = format!("{}/api/v1/constants", self.config.book_catalog.url);
-        self.make_request(&url, &self.config.book_catalog.name, reqwest::Method::GET, None::<&()>, None::<&()>).await
+    pub async fn get_constants(&self) -> Result<Upstreamed<ConstantsSchema>, ApiError> {
+        self.fetch("/api/v1/constants", &self.book_catalog, reqwest::Method::GET, None::<&()>, None::<&()>).await
     }
 
-    pub async fn get_author(&self, id: u64) -> Result<Author, ApiError> {
-        let url = format!("{}/api/v1/authors/{}", self.config.book_catalog.url, id);
-        self.make_request(&url, &self.config.book_catalog.name, reqwest::Method::GET, None::<&()>, None::<&()>).await
+    pub async fn get_author(&self, id: u64) -> Result<Upstreamed<Author>, ApiError> {
+        let path = format!("/api/v1/authors/{}", id);
+        self.fetch(&path, &self.book_catalog, reqwest::Method::GET, None::<&()>, None::<&()>).await
     }
 
-    pub async fn get_chapter(&self, book_id: u64, chapter_id: InputChapterSchema) -> Result<ChapterFullSchema, ApiError> {
-        let url = format!("{}/api/v1/books/{}/chapter", self.config.book_catalog.url, book_id);
-        self.make_request(&url, &self.config.book_catalog.name, reqwest::Method::GET, Some(&chapter_id), None::<&()>).await
+    pub async fn get_chapter(&self, book_id: u64, chapter_id: InputChapterSchema) -> Result<Upstreamed<ChapterFullSchema>, ApiError> {
+        let path = format!("/api/v1/books/{}/chapter", book_id);
+        self.fetch(&path, &self.book_catalog, reqwest::Method::GET, Some(&chapter_id), None::<&()>).await
     }
 
-    pub async fn get_chapters_list(&self, book_id: u64) -> Result<Vec<ChapterFullSchema>, ApiError> {
-        let url = format!("{}/api/v1/books/{}/chapters", self.config.book_catalog.url, book_id);
-        self.make_request(&url, &self.config.book_catalog.name, reqwest::Method::GET, None::<&()>, None::<&()>).await
+    pub async fn get_chapters_list(&self, book_id: u64) -> Result<Upstreamed<Vec<ChapterFullSchema>>, ApiError> {
+        let path = format!("/api/v1/books/{}/chapters", book_id);
+        self.fetch(&path, &self.book_catalog, reqwest::Method::GET, None::<&()>, None::<&()>).await
     }
 
     pub async fn rate(&self, schema: &RateInputSchema, user_id: i32) -> Result<(), ApiError> {
-        let url = format!("{}/ratings/rate", self.config.ratings.url);
+        if !self.ratings.breaker.try_acquire() {
+            tracing::warn!("Circuit breaker for {} is open, rejecting rating", self.ratings.name());
+            return Err(ApiError::ServiceUnavailable(self.ratings.name().to_owned()))
+        }
+
+        let endpoint = self.ratings.select();
+        let url = format!("{}/ratings/rate", endpoint.url());
 
-        let result = self.client
-            .post(&url)
+        // Not idempotent, so never retried
+        let result = self
+            .request(reqwest::Method::POST, &url)
+            .timeout(self.ratings.policy().timeout())
             .json(&RateOutputSchema {
                 score: schema.score,
                 item_id: schema.item_id,
@@ -272,6 +386,8 @@ impl ServiceClient {
             .send()
             .await;
 
+        record_outcome(&self.ratings, &result);
+
         match result {
             Ok(response) => {
                 if response.status().is_success() {
@@ -279,7 +395,7 @@ impl ServiceClient {
                 } else {
                     Err(ApiError::ServiceError(format!(
                         "{} returned error status: {}. Message: {}",
-                        self.config.ratings.name, 
+                        self.ratings.name(), 
                         response.status(),
                         response.text().await.unwrap_or_default()
                     )))
@@ -292,46 +408,149 @@ impl ServiceClient {
         }
     }
 
+    /// Starts a request carrying the trace context of the current span.
+    fn request<U: IntoUrl>(&self, method: reqwest::Method, url: U) -> RequestBuilder {
+        telemetry::otel::trace_context_headers()
+            .into_iter()
+            .fold(self.client.request(method, url), |request, (name, value)| request.header(name, value))
+    }
+
     #[inline]
-    async fn make_request<T, Q, J>(&self, url: &str, service_name: &str, method: reqwest::Method, query: Option<&Q>, json: Option<&J>) -> Result<T, ApiError>
+    async fn make_request<T, Q, J>(&self, path: &str, upstream: &Upstream, method: reqwest::Method, query: Option<&Q>, json: Option<&J>) -> Result<T, ApiError>
     where
         T: for<'de> serde::Deserialize<'de>,
         Q: serde::Serialize + ?Sized,
         J: serde::Serialize
     {
-        let method_str = method.as_str().to_owned();
-        let mut request = self.client.request(method, url);
-        
-        if let Some(q) = query {
-            request = request.query(q);
-        }
+        self.fetch(path, upstream, method, query, json)
+            .await
+            .map(|res| res.body)
+    }
 
-        if let Some(json) = json {
-            request = request.json(json);
+    /// Sends a request to an instance of the upstream with its timeout, retrying idempotent
+    /// methods on connection errors and 5xx responses. Fails fast while its circuit breaker is open.
+    async fn fetch<T, Q, J>(&self, path: &str, upstream: &Upstream, method: reqwest::Method, query: Option<&Q>, json: Option<&J>) -> Result<Upstreamed<T>, ApiError>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+        Q: serde::Serialize + ?Sized,
+        J: serde::Serialize
+    {
+        if !upstream.breaker.try_acquire() {
+            tracing::warn!("Circuit breaker for {} is open, rejecting {} {}", upstream.name(), method, path);
+            return Err(ApiError::ServiceUnavailable(upstream.name().to_owned()))
         }
-        
-        let response = request
-            .send()
-            .await
+
+        let policy = upstream.policy();
+        let retries = if method.is_idempotent() { policy.retries } else { 0 };
+        let mut attempt = 0;
+
+        let result = loop {
+            let endpoint = upstream.select();
+            let url = format!("{}{}", endpoint.url(), path);
+
+            let mut request = self
+                .request(method.clone(), &url)
+                .timeout(policy.timeout());
+
+            if let Some(q) = query {
+                request = request.query(q);
+            }
+
+            if let Some(json) = json {
+                request = request.json(json);
+            }
+
+            let result = request.send().await;
+
+            let failure = match &result {
+                Ok(response) if response.status().is_server_error() => format!("status {}", response.status()),
+                Ok(_) => {
+                    upstream.breaker.record_success();
+                    break result
+                },
+                Err(e) => format!("{:?}", e),
+            };
+
+            upstream.breaker.record_failure();
+
+            if attempt >= retries || !upstream.breaker.try_acquire() {
+                break result
+            }
+
+            attempt += 1;
+            metrics::counter!("gateway.upstream.retries.total", "upstream" => upstream.name().to_owned()).increment(1);
+            tracing::warn!("Retrying {} {} ({}/{}) after {}", method, url, attempt, retries, failure);
+
+            tokio::time::sleep(policy.backoff(attempt)).await;
+        };
+
+        let response = result
             .map_err(|e| {
-                tracing::error!("Failed to call {} {}: {:?}", method_str, url, e);
+                tracing::error!("Failed to call {} {} on {}: {:?}", method, path, upstream.name(), e);
                 ApiError::ServiceError("Failed to make request".to_owned())
             })?;
             
         if response.status().is_success() {
-            response.json::<T>()
+            let cache_headers = CacheHeaders::from_response(&response);
+
+            let body = response.json::<T>()
                 .await
                 .map_err(|e| {
-                    tracing::error!("Failed to deserialize response from {}: {:?}", service_name, e);
-                    ApiError::ServiceError(format!("Invalid response from {}", service_name))
-                })
+                    tracing::error!("Failed to deserialize response from {}: {:?}", upstream.name(), e);
+                    ApiError::ServiceError(format!("Invalid response from {}", upstream.name()))
+                })?;
+
+            Ok(Upstreamed { body, cache_headers })
         } else {
             Err(ApiError::ServiceError(format!(
                 "{} returned error status: {}. Message: {}",
-                service_name, 
+                upstream.name(), 
                 response.status(),
                 response.text().await.unwrap_or_default()
             )))
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header::{self, HeaderValue}, HttpResponse};
+
+    use super::CacheHeaders;
+
+    fn values(res: &HttpResponse, name: header::HeaderName) -> Vec<&str> {
+        res.headers()
+            .get_all(name)
+            .map(|value| value.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn merged_cache_headers_are_passed_on() {
+        let book = CacheHeaders {
+            cache_control: vec![HeaderValue::from_static("public, max-age=120")],
+            vary: Vec::new(),
+        };
+        let rating = CacheHeaders {
+            cache_control: vec![HeaderValue::from_static("max-age=30")],
+            vary: vec![HeaderValue::from_static("Authorization")],
+        };
+
+        let res = book.merge(rating)
+            .apply(&mut HttpResponse::Ok())
+            .finish();
+
+        assert_eq!(values(&res, header::CACHE_CONTROL), ["public, max-age=120", "max-age=30"]);
+        assert_eq!(values(&res, header::VARY), ["Authorization"]);
+    }
+
+    #[test]
+    fn empty_cache_headers_add_nothing() {
+        let res = CacheHeaders::default()
+            .apply(&mut HttpResponse::Ok())
+            .finish();
+
+        assert!(res.headers().get(header::CACHE_CONTROL).is_none());
+        assert!(res.headers().get(header::VARY).is_none());
+    }
 }
\ No newline at end of file
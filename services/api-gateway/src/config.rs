@@ -1,5 +1,9 @@
-use serde::Deserialize;
+use std::{net::IpAddr, str::FromStr, time::Duration};
+
+use secrecy::SecretBox;
+use serde::{Deserialize, Deserializer};
 use serde_aux::field_attributes::deserialize_number_from_string;
+use telemetry::{otel::OtlpSettings, output::LogSettings};
 
 #[derive(Deserialize, Debug)]
 pub struct Settings {
@@ -7,6 +11,11 @@ pub struct Settings {
     pub services: ServicesSettings,
     pub auth: AuthSettings,
     pub cache: CacheSettings,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+    pub otlp: Option<OtlpSettings>,
+    #[serde(default)]
+    pub log: LogSettings,
 }
 
 #[derive(Deserialize, Debug)]
@@ -25,12 +34,163 @@ pub struct ServicesSettings {
 #[derive(Deserialize, Debug)]
 pub struct ServiceSettings {
     pub name: String,
-    pub url: String,
+    /// Instances of the service, either a list or a comma-separated string
+    /// so they can be set from one environment variable.
+    #[serde(alias = "url", deserialize_with = "deserialize_urls")]
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub balancing: Balancing,
+    #[serde(default)]
+    pub health_check: HealthCheckSettings,
+    #[serde(default)]
+    pub resilience: ResilienceSettings,
+}
+
+fn deserialize_urls<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Urls {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let urls = match Urls::deserialize(deserializer)? {
+        Urls::One(urls) => urls.split(',').map(|url| url.trim().to_owned()).collect(),
+        Urls::Many(urls) => urls,
+    };
+
+    let urls = urls
+        .into_iter()
+        .filter(|url| !url.is_empty())
+        .map(|url| url.trim_end_matches('/').to_owned())
+        .collect::<Vec<_>>();
+
+    if urls.is_empty() {
+        return Err(serde::de::Error::custom("at least one url is required"))
+    }
+
+    Ok(urls)
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Balancing {
+    #[default]
+    RoundRobin,
+    /// Picks the instance with the fewest requests in flight
+    LeastInFlight,
+}
+
+/// Active polling of every instance. Instances failing `unhealthy_threshold`
+/// checks in a row are taken out of rotation until they pass `healthy_threshold`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HealthCheckSettings {
+    pub enabled: bool,
+    pub path: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub unhealthy_threshold: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub healthy_threshold: u32,
+}
+
+impl HealthCheckSettings {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+impl Default for HealthCheckSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: "/health".to_owned(),
+            interval_ms: 5000,
+            timeout_ms: 1000,
+            unhealthy_threshold: 2,
+            healthy_threshold: 1,
+        }
+    }
+}
+
+/// Timeouts, retries and circuit breaking applied to calls to one upstream.
+/// Only idempotent requests are retried.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ResilienceSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every following one
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub backoff_ms: u64,
+    pub circuit_breaker: CircuitBreakerSettings,
+}
+
+impl ResilienceSettings {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << attempt.saturating_sub(1).min(16)))
+    }
+}
+
+impl Default for ResilienceSettings {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 5000,
+            retries: 2,
+            backoff_ms: 100,
+            circuit_breaker: CircuitBreakerSettings::default(),
+        }
+    }
+}
+
+/// The breaker opens after `failure_threshold` consecutive failures and lets
+/// a single trial request through once `open_ms` has passed.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub failure_threshold: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub open_ms: u64,
+}
+
+impl CircuitBreakerSettings {
+    pub fn open_duration(&self) -> Duration {
+        Duration::from_millis(self.open_ms)
+    }
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_ms: 30_000,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct AuthSettings {
-    pub url: String
+    pub url: String,
+    /// Lets the service check token revocations with auth-service
+    pub internal_token: Option<SecretBox<String>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -38,6 +198,102 @@ pub struct CacheSettings {
     pub url: String
 }
 
+/// Requests are counted per user when a valid token is present, per client IP otherwise.
+/// The first rule matching a request applies; requests matching none are not limited.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct RateLimitSettings {
+    #[serde(default)]
+    pub rules: Vec<RateLimitRule>,
+    /// Reverse proxies in front of the gateway, e.g. `10.0.0.0/8`. `X-Forwarded-For`
+    /// is only read on requests from them, since clients can send any value.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpRange>,
+}
+
+/// An address or a CIDR block.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            },
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            },
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.split_once('/').unwrap_or((s, ""));
+
+        let addr = addr.trim()
+            .parse::<IpAddr>()
+            .map_err(|e| format!("Invalid address {}: {}", s, e))?;
+
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix.trim() {
+            "" => max_prefix,
+            prefix => prefix.parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("Invalid prefix length in {}", s))?,
+        };
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RateLimitRule {
+    /// Path prefix matched on whole segments, e.g. `/search` covers `/search/books` but not `/searches`
+    pub path: String,
+    /// Methods the rule applies to, all of them if empty
+    #[serde(default)]
+    pub methods: Vec<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub limit: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_secs: u64,
+}
+
+impl RateLimitRule {
+    pub fn matches(&self, method: &str, path: &str) -> bool {
+        let path_matches = path
+            .strip_prefix(self.path.trim_end_matches('/'))
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+
+        path_matches
+            && (self.methods.is_empty() || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method)))
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+}
+
 pub fn get_config() -> Result<Settings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
     let config_dir = base_path.join("configuration");
@@ -83,4 +339,69 @@ impl TryFrom<String> for Environment {
             other => Err(format!("{} is not a supported environment", other)),
         }
     }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{IpRange, RateLimitRule};
+
+    fn rule(path: &str, methods: &[&str]) -> RateLimitRule {
+        RateLimitRule {
+            path: path.to_string(),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            limit: 10,
+            window_secs: 60,
+        }
+    }
+
+    #[test]
+    fn rate_limit_rule_matches_whole_segments() {
+        let rule = rule("/search", &[]);
+
+        assert!(rule.matches("GET", "/search"));
+        assert!(rule.matches("GET", "/search/books"));
+        assert!(!rule.matches("GET", "/searches"));
+        assert!(!rule.matches("GET", "/books/search"));
+    }
+
+    #[test]
+    fn rate_limit_rule_ignores_trailing_slash() {
+        assert!(rule("/ratings/", &[]).matches("POST", "/ratings/rate"));
+        assert!(rule("/", &[]).matches("GET", "/books/1"));
+    }
+
+    #[test]
+    fn rate_limit_rule_filters_methods() {
+        let rule = rule("/ratings/rate", &["post"]);
+
+        assert!(rule.matches("POST", "/ratings/rate"));
+        assert!(!rule.matches("GET", "/ratings/rate"));
+    }
+
+    #[test]
+    fn ip_range_matches_addresses_in_block() {
+        let range = "172.16.0.0/12".parse::<IpRange>().unwrap();
+
+        assert!(range.contains("172.20.1.5".parse().unwrap()));
+        assert!(range.contains("::ffff:172.20.1.5".parse().unwrap()));
+        assert!(!range.contains("172.32.0.1".parse().unwrap()));
+        assert!(!range.contains("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn ip_range_without_prefix_is_a_single_address() {
+        let range = "fd00::1".parse::<IpRange>().unwrap();
+
+        assert!(range.contains("fd00::1".parse().unwrap()));
+        assert!(!range.contains("fd00::2".parse().unwrap()));
+        assert!("0.0.0.0/0".parse::<IpRange>().unwrap().contains("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn ip_range_rejects_invalid_values() {
+        for value in ["10.0.0.0/33", "10.0.0/8", "localhost", "10.0.0.0/x"] {
+            assert!(value.parse::<IpRange>().is_err(), "{}", value);
+        }
+    }
 }
\ No newline at end of file
@@ -9,6 +9,9 @@ pub enum ApiError {
     
     #[error("Backend service error: {0}")]
     ServiceError(String),
+
+    #[error("{0} is temporarily unavailable")]
+    ServiceUnavailable(String),
     
     #[error("Invalid request parameters")]
     ValidationError,
@@ -22,6 +25,7 @@ impl actix_web::error::ResponseError for ApiError {
         match self {
             Self::ConfigError => HttpResponse::InternalServerError().finish(),
             Self::ServiceError(msg) => HttpResponse::BadGateway().json(msg),
+            Self::ServiceUnavailable(_) => HttpResponse::ServiceUnavailable().json(self.to_string()),
             Self::ValidationError => HttpResponse::BadRequest().body("Invalid parameters"),
             Self::NotFound => HttpResponse::NotFound().finish(),
         }
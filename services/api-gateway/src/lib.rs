@@ -1,6 +1,8 @@
 pub mod config;
 pub mod startup;
 pub mod client;
+pub mod upstream;
+pub mod rate_limit;
 pub mod error;
 pub mod schema;
 pub mod routes;
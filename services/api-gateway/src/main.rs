@@ -1,8 +1,11 @@
-use api_gateway::{auth::jwt::JwtValidator, client::ServiceClient};
+use api_gateway::client::ServiceClient;
+use auth_client::jwt::JwtValidator;
 use api_gateway::config::get_config;
 use bb8_redis::bb8::Pool;
 use bb8_redis::RedisConnectionManager;
-use telemetry::{get_subscriber, init_subscriber};
+use bb8_redis::redis::Client;
+use secrecy::ExposeSecret;
+use telemetry::{get_subscriber, init_subscriber, otel::{init_tracer_provider, shutdown_tracer_provider}};
 use std::net::TcpListener;
 use std::time::Duration;
 
@@ -10,14 +13,20 @@ use api_gateway::startup::run;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let subscriber = get_subscriber("api-gateway".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-
     let config = get_config().unwrap();
 
+    let tracer_provider = config.otlp
+        .as_ref()
+        .map(|otlp| init_tracer_provider(env!("CARGO_PKG_NAME"), otlp).expect("Failed to build OTLP exporter"));
+
+    let (subscriber, log_filter) = get_subscriber("api-gateway".into(), "info".into(), std::io::stdout, &config.log, tracer_provider.as_ref());
+    init_subscriber(subscriber);
+
     let client = ServiceClient::new(config.services);
+    client.spawn_health_checks();
 
-    let jwt_validator = JwtValidator::new(config.auth.url);
+    let jwt_validator = JwtValidator::new(config.auth.url)
+        .internal_token(config.auth.internal_token.map(|token| token.expose_secret().clone()));
 
     let address = format!("{}:{}", config.application.host, config.application.port);
 
@@ -30,6 +39,12 @@ async fn main() -> std::io::Result<()> {
             .build(redis_manager)
             .await
             .expect("Failed to build Redis pool");
+    let redis_client = Client::open(config.cache.url.clone())
+        .expect("Failed to create Redis client");
+
+    let result = run(listener, client, jwt_validator, redis_pool, redis_client, config.rate_limit, log_filter)?.await;
+
+    shutdown_tracer_provider(tracer_provider);
 
-    run(listener, client, jwt_validator, redis_pool)?.await
+    result
 }
\ No newline at end of file
@@ -0,0 +1,402 @@
+use std::{future::{ready, Ready}, net::IpAddr, rc::Rc, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use actix_web::{body::{EitherBody, MessageBody}, dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, http::{header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER}, StatusCode}, Error, HttpMessage, HttpResponse};
+use auth_client::jwt::Claims;
+use bb8_redis::{bb8::{Pool, RunError}, redis::{self, RedisError}, RedisConnectionManager};
+use futures_util::future::LocalBoxFuture;
+use thiserror::Error;
+
+use crate::config::{IpRange, RateLimitRule, RateLimitSettings};
+
+const KEY_PREFIX: &str = "api-gateway:rate-limit";
+
+/// Counts the request in the current window unless that would exceed the limit, in a
+/// single step so that concurrent requests can't overshoot it.
+/// KEYS: current and previous window. ARGV: limit, weight of the previous window, expiry in ms.
+/// Returns whether the request is allowed and the counts of both windows.
+const CHECK_SCRIPT: &str = r#"
+    local count = tonumber(redis.call('GET', KEYS[1]) or '0')
+    local previous = tonumber(redis.call('GET', KEYS[2]) or '0')
+
+    if previous * tonumber(ARGV[2]) + count + 1 > tonumber(ARGV[1]) then
+        return {0, count, previous}
+    end
+
+    count = redis.call('INCR', KEYS[1])
+    redis.call('PEXPIRE', KEYS[1], ARGV[3])
+
+    return {1, count, previous}
+"#;
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error("Redis pool error: {0}")]
+    Pool(#[from] RunError<RedisError>),
+    #[error("Redis error: {0}")]
+    Redis(#[from] RedisError),
+}
+
+struct Decision {
+    allowed: bool,
+    limit: u64,
+    remaining: u64,
+    reset: Duration,
+    retry_after: Duration,
+}
+
+impl Decision {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        let values = [
+            ("x-ratelimit-limit", self.limit),
+            ("x-ratelimit-remaining", self.remaining),
+            ("x-ratelimit-reset", ceil_secs(self.reset)),
+        ];
+
+        for (name, value) in values {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        }
+
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(ceil_secs(self.retry_after).max(1)));
+        }
+    }
+}
+
+/// Sliding window rate limiter shared by all gateway instances through Redis.
+/// Must be wrapped inside the JWT middleware to count requests per user.
+/// Requests are let through if Redis is unavailable.
+pub struct RateLimiter {
+    pool: Pool<RedisConnectionManager>,
+    settings: RateLimitSettings,
+}
+
+impl RateLimiter {
+    pub fn new(pool: Pool<RedisConnectionManager>, settings: RateLimitSettings) -> Self {
+        Self { pool, settings }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimiterService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterService {
+            service: Rc::new(service),
+            pool: self.pool.clone(),
+            rules: self.settings.rules.clone().into(),
+            trusted_proxies: self.settings.trusted_proxies.clone().into(),
+        }))
+    }
+}
+
+pub struct RateLimiterService<S> {
+    service: Rc<S>,
+    pool: Pool<RedisConnectionManager>,
+    rules: Rc<[RateLimitRule]>,
+    trusted_proxies: Rc<[IpRange]>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let pool = self.pool.clone();
+        let rules = self.rules.clone();
+        let trusted_proxies = self.trusted_proxies.clone();
+
+        Box::pin(async move {
+            let rule = match rules.iter().find(|rule| rule.matches(req.method().as_str(), req.path())) {
+                Some(rule) => rule,
+                None => return Ok(service.call(req).await?.map_into_left_body()),
+            };
+
+            let user = req.extensions().get::<Claims>().map(|claims| claims.sub.clone());
+            let subject = match (user, client_ip(req.peer_addr().map(|addr| addr.ip()), req.headers(), &trusted_proxies)) {
+                (Some(sub), _) => format!("user:{}", sub),
+                (None, Some(ip)) => format!("ip:{}", ip),
+                (None, None) => "ip:unknown".to_string(),
+            };
+
+            let decision = match check(&pool, rule, &subject).await {
+                Ok(decision) => decision,
+                Err(e) => {
+                    tracing::error!("Failed to check rate limit: {:?}", e);
+                    return Ok(service.call(req).await?.map_into_left_body())
+                },
+            };
+
+            if !decision.allowed {
+                tracing::warn!("Rate limit of {} exceeded by {}", rule.path, subject);
+
+                let (req, _) = req.into_parts();
+                let mut response = HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+                    .json(serde_json::json!({
+                        "error": "Too Many Requests",
+                        "message": "Rate limit exceeded"
+                    }));
+                decision.write_headers(response.headers_mut());
+
+                return Ok(ServiceResponse::new(req, response).map_into_right_body())
+            }
+
+            let mut res = service.call(req).await?;
+            decision.write_headers(res.headers_mut());
+
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+/// Walks `X-Forwarded-For` from the right, starting at the peer, for as long as the
+/// hops are trusted proxies. The first untrusted hop is the client, anything to its left
+/// may have been made up by the client.
+fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted_proxies: &[IpRange]) -> Option<IpAddr> {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|range| range.contains(ip));
+
+    let mut client = peer?;
+
+    if !is_trusted(client) {
+        return Some(client)
+    }
+
+    let forwarded = headers.get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+
+    for hop in forwarded.into_iter().rev() {
+        match hop.parse::<IpAddr>() {
+            Ok(ip) => client = ip,
+            // Keep the last address we could vouch for
+            Err(_) => break,
+        }
+
+        if !is_trusted(client) {
+            break
+        }
+    }
+
+    Some(client)
+}
+
+/// Counts the request in the current window and estimates the rate by weighting
+/// the previous window's count by how much of it still overlaps the sliding window.
+/// Rejected requests are not counted.
+async fn check(
+    pool: &Pool<RedisConnectionManager>,
+    rule: &RateLimitRule,
+    subject: &str,
+) -> Result<Decision, RateLimitError> {
+    let window = rule.window().as_millis().max(1) as u64;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let (index, elapsed) = (now / window, now % window);
+
+    let key = format!("{}:{}:{}", KEY_PREFIX, rule.path, subject);
+    let current_key = format!("{}:{}", key, index);
+    let previous_key = format!("{}:{}", key, index.saturating_sub(1));
+
+    let mut con = pool.get().await?;
+
+    let (allowed, count, previous): (bool, u64, u64) = redis::cmd("EVAL")
+        .arg(CHECK_SCRIPT)
+        .arg(2)
+        .arg(&current_key)
+        .arg(&previous_key)
+        .arg(rule.limit)
+        .arg(previous_weight(elapsed, window))
+        .arg(window * 2)
+        .query_async(&mut *con)
+        .await?;
+
+    Ok(decide(rule.limit, allowed, count, previous, elapsed, window))
+}
+
+/// Share of the previous window still covered by the sliding window.
+fn previous_weight(elapsed: u64, window: u64) -> f64 {
+    1.0 - elapsed as f64 / window as f64
+}
+
+/// Builds the decision from the counts of both windows, `count` including
+/// the request only if it was allowed.
+fn decide(limit: u64, allowed: bool, count: u64, previous: u64, elapsed: u64, window: u64) -> Decision {
+    let estimated = previous as f64 * previous_weight(elapsed, window) + count as f64;
+
+    let retry_after = if allowed {
+        Duration::ZERO
+    } else {
+        retry_after(limit as f64, count as f64, previous as f64, elapsed as f64, window as f64)
+    };
+
+    Decision {
+        allowed,
+        limit,
+        remaining: (limit as f64 - estimated).max(0.0) as u64,
+        reset: Duration::from_millis(window - elapsed),
+        retry_after,
+    }
+}
+
+/// Time until one more request fits under `limit`, assuming no other requests arrive.
+fn retry_after(limit: f64, count: f64, previous: f64, elapsed: f64, window: f64) -> Duration {
+    let room = limit - 1.0;
+
+    let wait = if count <= room && previous > 0.0 {
+        // The previous window's weight drops enough before the current one ends
+        window * (1.0 - (room - count) / previous) - elapsed
+    } else {
+        // Only the current window's count, weighted down after it ends, remains
+        window - elapsed + window * (1.0 - room / count.max(1.0)).max(0.0)
+    };
+
+    Duration::from_millis(wait.max(0.0).ceil() as u64)
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_millis().div_ceil(1000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use std::time::Duration;
+
+    use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+
+    use super::{client_ip, decide, previous_weight, retry_after};
+
+    const WINDOW: f64 = 60_000.0;
+
+    fn forwarded_for(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(HeaderName::from_static("x-forwarded-for"), HeaderValue::from_static(value));
+        headers
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_peer_is_the_client() {
+        let headers = forwarded_for("1.2.3.4");
+
+        assert_eq!(client_ip(ip("203.0.113.7"), &headers, &[]), ip("203.0.113.7"));
+        assert_eq!(client_ip(ip("203.0.113.7"), &headers, &["10.0.0.0/8".parse().unwrap()]), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn spoofed_entries_before_the_proxy_hop_are_ignored() {
+        // The client sent `1.2.3.4`, nginx appended the address it saw
+        let headers = forwarded_for("1.2.3.4, 203.0.113.7");
+
+        assert_eq!(client_ip(ip("10.0.0.2"), &headers, &["10.0.0.0/8".parse().unwrap()]), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn chained_trusted_proxies_are_skipped() {
+        let headers = forwarded_for("203.0.113.7, 10.0.0.3");
+
+        assert_eq!(client_ip(ip("10.0.0.2"), &headers, &["10.0.0.0/8".parse().unwrap()]), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn malformed_hops_fall_back_to_the_last_trusted_address() {
+        let headers = forwarded_for("203.0.113.7, garbage");
+
+        assert_eq!(client_ip(ip("10.0.0.2"), &headers, &["10.0.0.0/8".parse().unwrap()]), ip("10.0.0.2"));
+    }
+    /// Sliding window estimate `at` ms into the current window, which may lie in the next one.
+    fn estimate_at(count: f64, previous: f64, at: f64) -> f64 {
+        if at < WINDOW {
+            previous * (1.0 - at / WINDOW) + count
+        } else {
+            count * (1.0 - (at - WINDOW) / WINDOW)
+        }
+    }
+
+    #[test]
+    fn previous_window_weighs_less_as_the_window_slides() {
+        assert_eq!(previous_weight(0, 60_000), 1.0);
+        assert_eq!(previous_weight(15_000, 60_000), 0.75);
+        assert_eq!(previous_weight(59_999, 60_000), 1.0 - 59_999.0 / 60_000.0);
+    }
+
+    #[test]
+    fn remaining_counts_the_weighted_previous_window() {
+        // 8 * 0.5 + 3
+        let decision = decide(10, true, 3, 8, 30_000, 60_000);
+
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 3);
+        assert_eq!(decision.reset, Duration::from_millis(30_000));
+        assert_eq!(decision.retry_after, Duration::ZERO);
+
+        let decision = decide(10, false, 6, 8, 30_000, 60_000);
+        assert_eq!(decision.remaining, 0);
+        assert!(decision.retry_after > Duration::ZERO);
+    }
+
+    #[test]
+    fn retry_after_waits_for_the_previous_window_to_slide_out() {
+        // 10 * (1 - t) + 5 <= 9 once t = 0.6
+        let wait = retry_after(10.0, 5.0, 10.0, 0.0, WINDOW);
+
+        assert_eq!(wait, Duration::from_millis(36_000));
+    }
+
+    #[test]
+    fn retry_after_waits_for_the_current_window_to_slide_out() {
+        // Full current window, the 10 requests must weigh at most 9 in the next one
+        let wait = retry_after(10.0, 10.0, 0.0, 15_000.0, WINDOW);
+
+        assert_eq!(wait, Duration::from_millis(45_000 + 6_000));
+    }
+
+    #[test]
+    fn retry_after_is_the_earliest_time_a_request_fits() {
+        let cases = [(10.0, 9.0, 4.0, 1_000.0), (10.0, 3.0, 40.0, 20_000.0), (5.0, 5.0, 5.0, 59_000.0), (1.0, 1.0, 1.0, 0.0)];
+
+        for (limit, count, previous, elapsed) in cases {
+            let wait = retry_after(limit, count, previous, elapsed, WINDOW).as_millis() as f64;
+            let room = limit - 1.0;
+
+            assert!(estimate_at(count, previous, elapsed + wait) <= room + 1e-9, "{:?}", (limit, count, previous, elapsed));
+            assert!(estimate_at(count, previous, elapsed + wait - 2.0) > room, "{:?}", (limit, count, previous, elapsed));
+        }
+    }
+
+    #[test]
+    fn rejections_advertise_at_least_a_second() {
+        let mut headers = HeaderMap::new();
+        let mut decision = decide(10, false, 10, 0, 59_999, 60_000);
+        decision.retry_after = Duration::from_millis(1);
+
+        decision.write_headers(&mut headers);
+
+        assert_eq!(headers.get(RETRY_AFTER).unwrap(), "1");
+        assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), "0");
+        assert_eq!(headers.get("x-ratelimit-reset").unwrap(), "1");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+use actix_web::{dev::PeerAddr, web, HttpRequest, HttpResponse};
+
+use crate::client::ServiceClient;
+
+pub async fn forward_cache_admin(
+    client: web::Data<ServiceClient>,
+    req: HttpRequest,
+    payload: web::Payload,
+    peer_addr: Option<PeerAddr>
+) -> Result<HttpResponse, actix_web::Error> {
+    client.forward_cache_admin(req, payload, peer_addr).await
+}
\ No newline at end of file
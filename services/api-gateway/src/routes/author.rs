@@ -7,7 +7,9 @@ pub async fn get_author(
     id: web::Path<u64>,
 ) -> impl Responder {
     match client.get_author(id.into_inner()).await {
-        Ok(author) => HttpResponse::Ok().json(author),
+        Ok(author) => author.cache_headers
+            .apply(&mut HttpResponse::Ok())
+            .json(author.body),
         Err(e) => e.error_response()
     }
 }
\ No newline at end of file
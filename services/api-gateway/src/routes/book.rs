@@ -1,4 +1,4 @@
-use actix_web::{web, HttpResponse, Responder, ResponseError};
+use actix_web::{http::header, web, HttpResponse, Responder, ResponseError};
 use serde_qs::actix::QsQuery;
 
 use crate::{auth::extractor::UserId, client::ServiceClient, schema::{Author, BookSchema, GetListSchema, SearchQuery}};
@@ -19,7 +19,11 @@ pub async fn get_book(
     user_id: UserId
 ) -> impl Responder {
     match client.get_book(id.into_inner(), user_id.0).await {
-        Ok(book) => HttpResponse::Ok().json(book),
+        // The book carries the caller's own rating
+        Ok(book) => book.cache_headers
+            .apply(&mut HttpResponse::Ok())
+            .append_header((header::VARY, "Authorization"))
+            .json(book.body),
         Err(e) => e.error_response()
     }
 }
@@ -29,7 +33,9 @@ pub async fn search_book(
     q: web::Query<SearchQuery>
 ) -> impl Responder {
     match client.search::<BookSchema>(q.into_inner(), "books").await {
-        Ok(books) => HttpResponse::Ok().json(books),
+        Ok(books) => books.cache_headers
+            .apply(&mut HttpResponse::Ok())
+            .json(books.body),
         Err(e) => e.error_response(),
     }
 }
@@ -39,7 +45,9 @@ pub async fn search_authors(
     q: web::Query<SearchQuery>
 ) -> impl Responder {
     match client.search::<Author>(q.into_inner(), "authors").await {
-        Ok(books) => HttpResponse::Ok().json(books),
+        Ok(books) => books.cache_headers
+            .apply(&mut HttpResponse::Ok())
+            .json(books.body),
         Err(e) => e.error_response(),
     }
 }
@@ -48,7 +56,9 @@ pub async fn get_constants(
     client: web::Data<ServiceClient>,
 ) -> impl Responder {
     match client.get_constants().await {
-        Ok(consts) => HttpResponse::Ok().json(consts),
+        Ok(consts) => consts.cache_headers
+            .apply(&mut HttpResponse::Ok())
+            .json(consts.body),
         Err(e) => e.error_response()
     }
 }
\ No newline at end of file
@@ -8,7 +8,9 @@ pub async fn get_chapter(
     query: web::Query<InputChapterSchema>
 ) -> impl Responder {
     match client.get_chapter(path.into_inner(), query.into_inner()).await {
-        Ok(chapter) => HttpResponse::Ok().json(chapter),
+        Ok(chapter) => chapter.cache_headers
+            .apply(&mut HttpResponse::Ok())
+            .json(chapter.body),
         Err(e) => e.error_response()
     }
 }
@@ -18,7 +20,9 @@ pub async fn get_chapters(
     path: web::Path<u64>
 ) -> impl Responder {
     match client.get_chapters_list(path.into_inner()).await {
-        Ok(chapters) => HttpResponse::Ok().json(chapters),
+        Ok(chapters) => chapters.cache_headers
+            .apply(&mut HttpResponse::Ok())
+            .json(chapters.body),
         Err(e) => e.error_response(),
     }
 }
\ No newline at end of file
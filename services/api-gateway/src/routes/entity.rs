@@ -28,7 +28,7 @@ pub async fn delete_entity(
         Ok(_) => HttpResponse::Ok().finish(),
         Err(e) => {
             tracing::error!("Failed to delete entity");
-            return e.error_response()
+            e.error_response()
         },
     }
 }
\ No newline at end of file
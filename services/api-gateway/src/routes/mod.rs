@@ -1,4 +1,6 @@
 use actix_web::web;
+use cache::{actix::CacheEntry, serializer::{bitcode::BitcodeSerializer, compressed::CompressedSerializer}};
+use admin::forward_cache_admin;
 use author::get_author;
 use book::{get_book, get_books, search_authors, search_book, get_constants};
 use chapter::{get_chapter, get_chapters};
@@ -11,7 +13,7 @@ pub mod author;
 pub mod entity;
 pub mod chapter;
 pub mod ratings;
-pub mod metrics;
+pub mod admin;
 
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg
@@ -20,7 +22,7 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
             .route("", web::get().to(get_books))
             .route("/{id}/chapter", web::get().to(get_chapter))
             .route("/{id}/chapters", web::get().to(get_chapters))
-            .route("/{id}", web::get().to(get_book)).wrap(JwtMiddleware::optional())
+            .route("/{id}", web::get().to(get_book))
             .service(
                 web::scope("")
                     .wrap(JwtMiddleware::admin_only())
@@ -51,8 +53,15 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
         )
         .service(
             web::scope("/ratings")
-                .wrap(JwtMiddleware::default())
+                .wrap(JwtMiddleware::require_scopes(vec!["ratings:write"]))
                 .route("/rate", web::post().to(rate))
         )
+        .service(
+            web::scope("/admin")
+                .wrap(JwtMiddleware::admin_only())
+                .service(cache::actix::admin::scope::<CacheEntry, CompressedSerializer<BitcodeSerializer<CacheEntry>>>("/cache"))
+                .route("/book-catalog/cache/{tail:.*}", web::route().to(forward_cache_admin))
+                .service(telemetry::log_filter::scope("/log-filter"))
+        )
         .route("/constants", web::get().to(get_constants));
 }
\ No newline at end of file
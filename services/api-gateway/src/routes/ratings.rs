@@ -1,9 +1,11 @@
 use actix_web::{web, HttpResponse, Responder, ResponseError};
+use cache::actix::ResponseCache;
 
 use crate::{auth::extractor::UserId, client::ServiceClient, schema::RateInputSchema};
 
 pub async fn rate(
     client: web::Data<ServiceClient>,
+    cache: web::Data<ResponseCache>,
     schema: web::Json<RateInputSchema>,
     user_id: UserId
 ) -> impl Responder {
@@ -16,7 +18,16 @@ pub async fn rate(
     };
 
     match client.rate(&schema, user_id).await {
-        Ok(_) => HttpResponse::Created().finish(),
+        Ok(_) => {
+            // Cached book pages include both the average and the caller's own rating
+            let tag = format!("books_{}", schema.item_id);
+
+            if let Err(e) = cache.invalidate_tag(&tag).await {
+                tracing::error!("Failed to invalidate cache tag {}: {:?}", tag, e);
+            }
+
+            HttpResponse::Created().finish()
+        },
         Err(e) => e.error_response()
     }
 }
\ No newline at end of file
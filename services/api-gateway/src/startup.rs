@@ -1,51 +1,164 @@
 use std::net::TcpListener;
 
 use actix_web::{dev::Server, web::{self, Data}, App, HttpResponse, HttpServer};
-use bb8_redis::{bb8::Pool, RedisConnectionManager};
-use cache::{actix::CacheMiddleware, cache::HybridCache, serializer::bitcode::BitcodeSerializer};
-use metrics_exporter_prometheus::PrometheusBuilder;
+use auth_client::jwt::{Claims, JwtValidator};
+use bb8_redis::{bb8::Pool, redis::Client, RedisConnectionManager};
+use cache::{actix::{generate_key_default, generate_tags_default, generate_user_key, CacheMiddleware, RequestContext}, cache::HybridCache, expiry::Expiration, serializer::{bitcode::BitcodeSerializer, compressed::{CompressedSerializer, Compression}}};
+use telemetry::{log_filter::LogFilterHandle, metrics::{install_recorder, metrics_handler, RequestMetrics}};
 use tracing_actix_web::TracingLogger;
 
-use crate::{auth::jwt::JwtValidator, client::ServiceClient, routes::{configure_routes, metrics::get_metrics}};
+use crate::{auth::middleware::JwtMiddleware, client::ServiceClient, config::RateLimitSettings, rate_limit::RateLimiter, routes::configure_routes};
 
 pub fn run(
     listener: TcpListener,
     client: ServiceClient,
     jwt_validator: JwtValidator,
     redis_pool: Pool<RedisConnectionManager>,
+    redis_client: Client,
+    rate_limit: RateLimitSettings,
+    log_filter: LogFilterHandle,
 ) -> Result<Server, std::io::Error> {
     let client = Data::new(client);
     let validator = Data::new(jwt_validator);
+    let log_filter = Data::new(log_filter);
 
-    let cache = HybridCache::new("api-gateway".to_string(), redis_pool, 2000, BitcodeSerializer::default());
+    let rate_limit_pool = redis_pool.clone();
 
-    let builder = PrometheusBuilder::new();
+    let cache = HybridCache::new(
+        "api-gateway".to_string(),
+        redis_pool,
+        2000,
+        CompressedSerializer::new(BitcodeSerializer::default(), Compression::Lz4)
+    )
+        .with_version(2)
+        .with_invalidation_channel(redis_client);
 
-    let handle = builder
-        .add_global_label("service", "api-gateway")
-        .install_recorder()
-        .expect("Failed to install metrics recorder");
+    let cache_data = Data::new(cache.clone());
 
-    let handle = Data::new(handle);
+    let handle = Data::new(install_recorder("api-gateway"));
 
     let server = HttpServer::new(move || {
         let cache_middleware = CacheMiddleware::new(cache.clone())
+            .max_cache_size(256 * 1024)
             .cache_condition(|ctx| {
-                ctx.method == "GET" && ctx.path != "/books"
+                ctx.method == "GET" && ctx.path != "/books" && !ctx.path.starts_with("/admin")
+            })
+            .soft_ttl(Expiration::Minutes(5))
+            .key_gen_fn(generate_key)
+            .tag_gen_fn(generate_tags)
+            .invalidate_condition(|ctx| {
+                // Rating writes name their book in the body, their handler invalidates it
+                matches!(ctx.method, "POST" | "PUT" | "DELETE")
+                    && !ctx.path.starts_with("/ratings")
+                    && !ctx.path.starts_with("/admin")
             });
         
         App::new()
             .wrap(cache_middleware)
+            .wrap(RateLimiter::new(rate_limit_pool.clone(), rate_limit.clone()))
+            // Claims must be known before the cache computes user-aware keys
+            // and the rate limiter counts requests per user
+            .wrap(JwtMiddleware::optional())
             .wrap(TracingLogger::default())
+            .wrap(RequestMetrics)
             .app_data(client.clone())
             .app_data(validator.clone())
+            .app_data(cache_data.clone())
+            .app_data(log_filter.clone())
             .app_data(handle.clone())
             .route("/health", web::to(HttpResponse::Ok))
-            .route("/metrics", web::get().to(get_metrics))
+            .route("/metrics", web::get().to(metrics_handler))
             .configure(configure_routes)
     })
     .listen(listener)?
     .run();
 
     Ok(server)
+}
+
+/// Book writes are multipart forms, so which authors they touch can't be told
+/// from the request. Author pages are tagged as a group instead.
+const AUTHOR_PAGES_TAG: &str = "author-pages";
+
+fn generate_tags(ctx: &RequestContext) -> Vec<String> {
+    let mut tags = generate_tags_default(ctx);
+    let resource = ctx.path
+        .split('/')
+        .find(|segment| !segment.is_empty());
+
+    // Any change to books or authors may affect search results
+    if ctx.method != "GET" || ctx.path.starts_with("/search") {
+        tags.push("search".to_string());
+    }
+
+    // Author pages list their books
+    match (ctx.method, resource) {
+        ("GET", Some("authors")) => tags.push(AUTHOR_PAGES_TAG.to_string()),
+        ("GET", _) => (),
+        (_, Some("books")) => tags.push(AUTHOR_PAGES_TAG.to_string()),
+        _ => (),
+    }
+
+    tags
+}
+
+/// `/books/{id}` includes the caller's own rating, so it is cached per user.
+fn generate_key(ctx: &RequestContext) -> String {
+    let mut segments = ctx.path
+        .split('/')
+        .filter(|segment| !segment.is_empty());
+
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("books"), Some(_), None) => generate_user_key::<Claims>(ctx),
+        _ => generate_key_default(ctx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{dev::Extensions, http::header::HeaderMap, web::BytesMut};
+    use cache::actix::LazyBody;
+
+    use super::*;
+
+    fn tags(method: &str, path: &str) -> Vec<String> {
+        let headers = HeaderMap::new();
+        let extensions = Extensions::new();
+        let body = LazyBody::new(BytesMut::new());
+
+        generate_tags(&RequestContext {
+            method,
+            path,
+            query_string: "",
+            headers: &headers,
+            extensions: &extensions,
+            body: &body,
+        })
+    }
+
+    #[test]
+    fn book_writes_evict_author_pages() {
+        let author_page = tags("GET", "/authors/3");
+
+        for (method, path) in [("PUT", "/books/5"), ("DELETE", "/books/5"), ("POST", "/books"), ("PUT", "/books/5/chapter")] {
+            let write = tags(method, path);
+
+            assert!(write.iter().any(|tag| author_page.contains(tag)), "{} {}: {:?}", method, path, write);
+        }
+    }
+
+    #[test]
+    fn book_writes_evict_the_book_and_search() {
+        let write = tags("PUT", "/books/5");
+
+        for page in [tags("GET", "/books/5"), tags("GET", "/books/5/chapters"), tags("GET", "/search/books")] {
+            assert!(write.iter().any(|tag| page.contains(tag)), "{:?}", page);
+        }
+    }
+
+    #[test]
+    fn book_pages_are_not_tagged_as_author_pages() {
+        assert_eq!(tags("GET", "/books/5"), vec!["books_5"]);
+        assert_eq!(tags("PUT", "/authors/3"), vec!["authors_3", "search"]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,325 @@
+use std::{sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex}, time::Instant};
+
+use futures_util::future::join_all;
+use reqwest::Client;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+use crate::config::{Balancing, CircuitBreakerSettings, ResilienceSettings, ServiceSettings};
+
+/// A backend service: its instances, how requests are spread over them
+/// and the state of its circuit breaker.
+pub struct Upstream {
+    pub settings: ServiceSettings,
+    pub breaker: CircuitBreaker,
+    endpoints: Vec<Endpoint>,
+    next: AtomicUsize,
+}
+
+struct Endpoint {
+    url: String,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+/// An instance picked for one request, counted as in flight until dropped.
+pub struct EndpointGuard<'a> {
+    endpoint: &'a Endpoint,
+}
+
+impl EndpointGuard<'_> {
+    pub fn url(&self) -> &str {
+        &self.endpoint.url
+    }
+}
+
+impl Drop for EndpointGuard<'_> {
+    fn drop(&mut self) {
+        self.endpoint.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Upstream {
+    pub fn new(settings: ServiceSettings) -> Self {
+        let breaker = CircuitBreaker::new(settings.name.clone(), settings.resilience.circuit_breaker.clone());
+        let endpoints = settings.urls
+            .iter()
+            .map(|url| Endpoint {
+                url: url.clone(),
+                healthy: AtomicBool::new(true),
+                in_flight: AtomicUsize::new(0),
+            })
+            .collect();
+
+        Self {
+            settings,
+            breaker,
+            endpoints,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.settings.name
+    }
+
+    pub fn policy(&self) -> &ResilienceSettings {
+        &self.settings.resilience
+    }
+
+    /// Picks an instance according to the balancing strategy. Unhealthy instances
+    /// are skipped unless none are healthy, in which case all of them are tried.
+    pub fn select(&self) -> EndpointGuard<'_> {
+        let count = self.endpoints.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+
+        let mut candidates = (0..count)
+            .map(|offset| &self.endpoints[(start + offset) % count])
+            .filter(|endpoint| endpoint.healthy.load(Ordering::Relaxed))
+            .peekable();
+
+        if candidates.peek().is_none() {
+            tracing::warn!("No healthy instances of {}, trying all of them", self.name());
+        }
+
+        let endpoint = match self.settings.balancing {
+            Balancing::RoundRobin => candidates.next(),
+            Balancing::LeastInFlight => candidates.min_by_key(|endpoint| endpoint.in_flight.load(Ordering::Relaxed)),
+        }
+        .unwrap_or(&self.endpoints[start % count]);
+
+        endpoint.in_flight.fetch_add(1, Ordering::Relaxed);
+
+        EndpointGuard { endpoint }
+    }
+
+    /// Polls every instance's health route on an interval and updates the rotation.
+    pub fn spawn_health_checks(self: Arc<Self>, client: Client) -> Option<JoinHandle<()>> {
+        let settings = self.settings.health_check.clone();
+
+        if !settings.enabled {
+            return None
+        }
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(settings.interval());
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            // Consecutive failed and passed checks per instance
+            let mut streaks = vec![(0u32, 0u32); self.endpoints.len()];
+
+            loop {
+                interval.tick().await;
+
+                let results = join_all(self.endpoints.iter().map(|endpoint| {
+                    client
+                        .get(format!("{}{}", endpoint.url, settings.path))
+                        .timeout(settings.timeout())
+                        .send()
+                })).await;
+
+                for ((endpoint, result), (failed, passed)) in self.endpoints.iter().zip(results).zip(streaks.iter_mut()) {
+                    let failure = match result {
+                        Ok(response) if response.status().is_success() => None,
+                        Ok(response) => Some(format!("status {}", response.status())),
+                        Err(e) => Some(format!("{:?}", e)),
+                    };
+
+                    if failure.is_none() {
+                        *failed = 0;
+                        *passed += 1;
+                    } else {
+                        *passed = 0;
+                        *failed += 1;
+                    }
+
+                    let healthy = endpoint.healthy.load(Ordering::Relaxed);
+
+                    if healthy && *failed >= settings.unhealthy_threshold {
+                        tracing::warn!(
+                            "Removing {} instance {} from rotation: {}",
+                            self.name(),
+                            endpoint.url,
+                            failure.unwrap_or_default()
+                        );
+                        endpoint.healthy.store(false, Ordering::Relaxed);
+                    } else if !healthy && *passed >= settings.healthy_threshold {
+                        tracing::info!("Returning {} instance {} to rotation", self.name(), endpoint.url);
+                        endpoint.healthy.store(true, Ordering::Relaxed);
+                    }
+
+                    metrics::gauge!(
+                        "gateway.upstream.endpoint.healthy",
+                        "upstream" => self.name().to_owned(),
+                        "endpoint" => endpoint.url.clone()
+                    )
+                    .set(if endpoint.healthy.load(Ordering::Relaxed) { 1.0 } else { 0.0 });
+                }
+            }
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// A trial request is in flight, another one is allowed after `retry_at`
+    /// in case the first never reports back.
+    HalfOpen { retry_at: Instant },
+}
+
+impl BreakerState {
+    fn as_gauge(&self) -> f64 {
+        match self {
+            BreakerState::Closed { .. } => 0.0,
+            BreakerState::HalfOpen { .. } => 1.0,
+            BreakerState::Open { .. } => 2.0,
+        }
+    }
+}
+
+/// Exported as `gateway.circuit_breaker.state`: 0 closed, 1 half-open, 2 open.
+pub struct CircuitBreaker {
+    service: String,
+    settings: CircuitBreakerSettings,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(service: String, settings: CircuitBreakerSettings) -> Self {
+        Self {
+            service,
+            settings,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a request may be sent now.
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let allowed = match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } | BreakerState::HalfOpen { retry_at: until } if now >= until => {
+                *state = BreakerState::HalfOpen { retry_at: now + self.settings.open_duration() };
+                true
+            },
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => false,
+        };
+
+        if !allowed {
+            metrics::counter!("gateway.circuit_breaker.rejections.total", "upstream" => self.service.clone())
+                .increment(1);
+        }
+
+        self.export(&state);
+
+        allowed
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        if !matches!(*state, BreakerState::Closed { failures: 0 }) {
+            if !matches!(*state, BreakerState::Closed { .. }) {
+                tracing::info!("Circuit breaker for {} closed", self.service);
+            }
+
+            *state = BreakerState::Closed { failures: 0 };
+        }
+
+        self.export(&state);
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        match *state {
+            BreakerState::Closed { failures } if failures + 1 < self.settings.failure_threshold => {
+                *state = BreakerState::Closed { failures: failures + 1 };
+            },
+            BreakerState::Open { .. } => {},
+            _ => {
+                tracing::warn!(
+                    "Circuit breaker for {} opened for {:?}",
+                    self.service,
+                    self.settings.open_duration()
+                );
+                *state = BreakerState::Open { until: now + self.settings.open_duration() };
+            },
+        }
+
+        self.export(&state);
+    }
+
+    fn export(&self, state: &BreakerState) {
+        metrics::gauge!("gateway.circuit_breaker.state", "upstream" => self.service.clone())
+            .set(state.as_gauge());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn breaker(open_ms: u64) -> CircuitBreaker {
+        CircuitBreaker::new("test".to_string(), CircuitBreakerSettings {
+            failure_threshold: 2,
+            open_ms,
+        })
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker(60_000);
+
+        breaker.record_failure();
+        assert!(breaker.try_acquire());
+
+        breaker.record_failure();
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let breaker = breaker(60_000);
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn lets_a_single_trial_through_after_open_duration() {
+        let breaker = breaker(10);
+
+        breaker.record_failure();
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(breaker.try_acquire());
+        assert!(!breaker.try_acquire());
+
+        breaker.record_success();
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn failed_trial_reopens() {
+        let breaker = breaker(10);
+
+        breaker.record_failure();
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(breaker.try_acquire());
+        breaker.record_failure();
+        assert!(!breaker.try_acquire());
+    }
+}
\ No newline at end of file
@@ -38,6 +38,7 @@ impl CodeStore {
         redirect_uri: String,
         code_challenge: String,
         code_challenge_method: String,
+        scope: String,
     ) -> Result<String> {
         let code = Uuid::new_v4().to_string();
         
@@ -49,6 +50,7 @@ impl CodeStore {
             expires_at: Utc::now() + chrono::Duration::seconds(self.code_expiry_seconds as i64),
             code_challenge,
             code_challenge_method,
+            scope,
         };
         
         let serialized = serde_json::to_string(&auth_code)
@@ -24,6 +24,8 @@ pub struct JwtService {
     issuer: String,
     pub access_token_lifetime: Duration,
     public_key_path: String,
+    scopes: Vec<String>,
+    default_scopes: Vec<String>,
 }
 
 impl JwtService {
@@ -37,10 +39,32 @@ impl JwtService {
             decoding_key: DecodingKey::from_rsa_pem(&public_key)?,
             issuer: auth_settings.issuer.clone(),
             access_token_lifetime: Duration::from_std(auth_settings.access_token_lifetime)?,
-            public_key_path: auth_settings.public_key_path.clone()
+            public_key_path: auth_settings.public_key_path.clone(),
+            scopes: auth_settings.scopes.clone(),
+            default_scopes: auth_settings.default_scopes.clone(),
         })
     }
 
+    /// Space-separated scopes granted for a requested `scope` parameter:
+    /// the supported ones among those requested, or the defaults if none were requested.
+    pub fn grant_scopes(&self, requested: Option<&str>) -> String {
+        let requested = requested.unwrap_or_default().split_whitespace().collect::<Vec<_>>();
+
+        if requested.is_empty() {
+            return self.default_scopes.join(" ")
+        }
+
+        let mut granted = Vec::new();
+
+        for scope in requested {
+            if self.scopes.iter().any(|s| s == scope) && !granted.contains(&scope) {
+                granted.push(scope);
+            }
+        }
+
+        granted.join(" ")
+    }
+
     pub fn create_access_token(
         &self,
         user_id: i32,
@@ -83,4 +107,42 @@ impl JwtService {
     pub fn get_public_key_path(&self) -> &str {
         &self.public_key_path
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> JwtService {
+        JwtService {
+            encoding_key: EncodingKey::from_secret(b"secret"),
+            decoding_key: DecodingKey::from_secret(b"secret"),
+            issuer: "test".to_string(),
+            access_token_lifetime: Duration::minutes(15),
+            public_key_path: String::new(),
+            scopes: ["read", "write", "admin"].map(str::to_string).to_vec(),
+            default_scopes: vec!["read".to_string()],
+        }
+    }
+
+    #[test]
+    fn grants_defaults_without_requested_scopes() {
+        assert_eq!(service().grant_scopes(None), "read");
+        assert_eq!(service().grant_scopes(Some("  ")), "read");
+    }
+
+    #[test]
+    fn grants_only_supported_scopes() {
+        assert_eq!(service().grant_scopes(Some("write delete read")), "write read");
+    }
+
+    #[test]
+    fn grants_repeated_scopes_once() {
+        assert_eq!(service().grant_scopes(Some("write  write")), "write");
+    }
+
+    #[test]
+    fn grants_nothing_for_unsupported_scopes() {
+        assert_eq!(service().grant_scopes(Some("delete")), "");
+    }
 }
\ No newline at end of file
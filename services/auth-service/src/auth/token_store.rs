@@ -7,7 +7,6 @@ use crate::schema::RefreshToken;
 
 pub struct TokenStore {
     redis_pool: Pool<RedisConnectionManager>,
-    access_token_ttl: u64,
     refresh_token_ttl: u64,
 }
 
@@ -28,7 +27,6 @@ impl TokenStore {
         // TODO: ttl from_config
         Self {
             redis_pool,
-            access_token_ttl: 60 * 15,
             refresh_token_ttl: 60 * 60 * 24 * 30,
         }
     }
@@ -37,8 +35,8 @@ impl TokenStore {
         format!("refresh_token:{}", token)
     }
 
-    fn get_access_key(&self, token: &str) -> String {
-        format!("access:{}", token)
+    fn get_revoked_key(&self, jti: &str) -> String {
+        format!("revoked_access:{}", jti)
     }
 
     pub async fn get_refresh_token(&self, token: &str) -> anyhow::Result<Option<RefreshToken>> {
@@ -91,13 +89,15 @@ impl TokenStore {
         Ok(())
     }
 
-    pub async fn revoke_access_token(&self, token: &str) -> anyhow::Result<()> {
+    /// Revokes the access token with the given `jti`. The entry only has to outlive
+    /// the token, so `ttl_seconds` is its remaining lifetime.
+    pub async fn revoke_access_token(&self, jti: &str, ttl_seconds: u64) -> anyhow::Result<()> {
         let mut conn = self.redis_pool.get().await.context("Failed to get Redis connection")?;
         
         conn.set_ex::<_, _, ()>(
-            self.get_access_key(token), 
+            self.get_revoked_key(jti), 
             "1", 
-            self.access_token_ttl
+            ttl_seconds.max(1)
         )
         .await
         .context("Failed to add token to revocation list")?;
@@ -105,11 +105,11 @@ impl TokenStore {
         Ok(())
     }
 
-    pub async fn is_access_token_revoked(&self, token: &str) -> anyhow::Result<bool> {
+    pub async fn is_access_token_revoked(&self, jti: &str) -> anyhow::Result<bool> {
         let mut conn = self.redis_pool.get().await.context("Failed to get Redis connection")?;
         
         let exists: bool = conn
-            .exists(self.get_access_key(token))
+            .exists(self.get_revoked_key(jti))
             .await
             .context("Failed to check token revocation status")?;
         
@@ -4,6 +4,7 @@ use secrecy::{SecretBox, ExposeSecret};
 use serde::{Deserialize, Deserializer};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::{postgres::{PgConnectOptions, PgSslMode}, ConnectOptions};
+use telemetry::{otel::OtlpSettings, output::LogSettings};
 
 #[derive(Deserialize, Debug)]
 pub struct Settings {
@@ -13,6 +14,9 @@ pub struct Settings {
     pub auth: AuthSettings,
     #[serde(default)]
     pub session: SessionSettings,
+    pub otlp: Option<OtlpSettings>,
+    #[serde(default)]
+    pub log: LogSettings,
 }
 
 #[derive(Deserialize, Debug)]
@@ -52,6 +56,14 @@ pub struct AuthSettings {
     pub private_key_path: String,
     pub public_key_path: String,
     pub issuer: String,
+    /// Scopes clients may request, others are dropped from the request
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Granted when a client requests no scope
+    #[serde(default)]
+    pub default_scopes: Vec<String>,
+    /// Shared with the services checking revocations, internal endpoints are refused while unset
+    pub internal_token: Option<SecretBox<String>>,
 }
 
 impl DatabaseSettings {
@@ -2,18 +2,22 @@ use actix_session::storage::RedisSessionStore;
 use auth_service::{auth::{client_store::ClientStore, code_store::CodeStore, jwt::JwtService, token_store::TokenStore}, config::get_config, services::user::UserService};
 use bb8_redis::{bb8::Pool, RedisConnectionManager};
 use sqlx::postgres::PgPoolOptions;
-use telemetry::{get_subscriber, init_subscriber};
+use telemetry::{get_subscriber, init_subscriber, otel::{init_tracer_provider, shutdown_tracer_provider}};
 use std::net::TcpListener;
 
 use auth_service::startup::run;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let subscriber = get_subscriber("auth-service".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-
     let config = get_config().unwrap();
 
+    let tracer_provider = config.otlp
+        .as_ref()
+        .map(|otlp| init_tracer_provider(env!("CARGO_PKG_NAME"), otlp).expect("Failed to build OTLP exporter"));
+
+    let (subscriber, log_filter) = get_subscriber("auth-service".into(), "info".into(), std::io::stdout, &config.log, tracer_provider.as_ref());
+    init_subscriber(subscriber);
+
     let connection_pool = PgPoolOptions::new()
         .connect_lazy_with(config.database.with_db());
 
@@ -48,7 +52,7 @@ async fn main() -> std::io::Result<()> {
 
     let user_service = UserService::new(connection_pool);
 
-    run(
+    let result = run(
         listener,
         jwt_service,
         token_store,
@@ -56,6 +60,11 @@ async fn main() -> std::io::Result<()> {
         user_service,
         client_store,
         redis_store,
-        config
-    )?.await
+        config,
+        log_filter
+    )?.await;
+
+    shutdown_tracer_provider(tracer_provider);
+
+    result
 }
\ No newline at end of file
@@ -20,7 +20,7 @@ async fn jwks(jwt_service: web::Data<JwtService>) -> impl Responder {
 }
 
 async fn get_jwks(jwt_service: &JwtService) -> anyhow::Result<serde_json::Value> {
-    let public_key_pem = fs::read_to_string(&jwt_service.get_public_key_path())?;
+    let public_key_pem = fs::read_to_string(jwt_service.get_public_key_path())?;
     
     let public_key = RsaPublicKey::from_public_key_pem(&public_key_pem)?;
     
@@ -1,27 +1,107 @@
 use actix_session::Session;
-use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web::{middleware::from_fn, web, HttpRequest, HttpResponse, Responder};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+use chrono::Utc;
 use urlencoding::encode;
 
-use crate::{auth::{client_store::ClientStore, code_store::CodeStore, jwt::JwtService, pkce, token_store::TokenStore}, schema::{AuthorizationRequest, ErrorResponse, OAuthTokenRequest, RefreshToken, TokenResponse}, services::user::UserService};
+use crate::{auth::{client_store::ClientStore, code_store::CodeStore, jwt::JwtService, pkce, token_store::TokenStore}, schema::{AuthorizationRequest, ErrorResponse, LogoutRequest, OAuthTokenRequest, RefreshToken, RevocationStatus, RevokeRequest, TokenResponse}, services::user::UserService, utils::{require_admin, require_internal_token, validate_access_token}};
 
 async fn verify_token(
     jwt_service: web::Data<JwtService>,
+    token_store: web::Data<TokenStore>,
     auth: BearerAuth
 ) -> impl Responder {
     let token = auth.token();
 
-    match jwt_service.validate_token(&token) {
+    match validate_access_token(token, &jwt_service, &token_store).await {
         Ok(claims) => HttpResponse::Ok().json(claims),
         Err(e) => HttpResponse::Unauthorized().body(e),
     }
 }
 
+/// Revokes the caller's access token and, if given, their refresh token.
+pub async fn logout(
+    auth: BearerAuth,
+    body: Option<web::Json<LogoutRequest>>,
+    jwt_service: web::Data<JwtService>,
+    token_store: web::Data<TokenStore>,
+) -> impl Responder {
+    let claims = match validate_access_token(auth.token(), &jwt_service, &token_store).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "invalid_token",
+                error_description: e,
+            });
+        }
+    };
+
+    let remaining = (claims.exp - Utc::now().timestamp()).max(0) as u64;
+
+    if let Err(e) = token_store.revoke_access_token(&claims.jti, remaining).await {
+        tracing::error!("Failed to revoke access token: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    let refresh_token = body.and_then(|body| body.into_inner().refresh_token);
+
+    if let Some(refresh_token) = refresh_token {
+        match token_store.get_refresh_token(&refresh_token).await {
+            Ok(Some(data)) if data.user_id.to_string() == claims.sub => {
+                if let Err(e) = token_store.invalidate_refresh_token(&refresh_token).await {
+                    tracing::error!("Failed to invalidate refresh token: {:?}", e);
+                    return HttpResponse::InternalServerError().finish();
+                }
+            },
+            Ok(_) => tracing::warn!("User {} tried to invalidate an unknown refresh token", claims.sub),
+            Err(e) => {
+                tracing::error!("Failed to get refresh token: {:?}", e);
+                return HttpResponse::InternalServerError().finish();
+            },
+        }
+    }
+
+    HttpResponse::NoContent().finish()
+}
+
+/// Revokes any access token by its `jti`. Admin only.
+pub async fn revoke(
+    body: web::Json<RevokeRequest>,
+    jwt_service: web::Data<JwtService>,
+    token_store: web::Data<TokenStore>,
+) -> impl Responder {
+    // No token issued with this jti can outlive the access token lifetime
+    let lifetime = jwt_service.access_token_lifetime.num_seconds().max(0) as u64;
+
+    match token_store.revoke_access_token(&body.jti, lifetime).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            tracing::error!("Failed to revoke access token: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Lets other services check tokens they have already validated. Internal only.
+pub async fn revocation_status(
+    jti: web::Path<String>,
+    token_store: web::Data<TokenStore>,
+) -> impl Responder {
+    match token_store.is_access_token_revoked(&jti).await {
+        Ok(revoked) => HttpResponse::Ok().json(RevocationStatus { revoked }),
+        Err(e) => {
+            tracing::error!("Failed to check token revocation: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 pub async fn authorize(
     req: HttpRequest,
     query: web::Query<AuthorizationRequest>,
     client_store: web::Data<ClientStore>,
     code_store: web::Data<CodeStore>,
+    jwt_service: web::Data<JwtService>,
     session: Session,
 ) -> impl Responder {
     let query = query.into_inner();
@@ -70,6 +150,7 @@ pub async fn authorize(
         query.redirect_uri.clone(),
         query.code_challenge,
         query.code_challenge_method,
+        jwt_service.grant_scopes(query.scope.as_deref()),
     ).await {
         Ok(code) => code,
         Err(e) => {
@@ -163,7 +244,7 @@ pub async fn exchange_token(
         
             let access_token = match jwt_service.create_access_token(
                 auth_code.user_id, 
-                "",
+                &auth_code.scope,
                 roles,
             ) {
                 Ok(token) => token,
@@ -175,6 +256,7 @@ pub async fn exchange_token(
             let refresh_token = match token_store.generate_refresh_token(RefreshToken {
                 user_id: auth_code.user_id,
                 fingerprint: req.fingerprint,
+                scope: Some(auth_code.scope.clone()),
             }).await {
                 Ok(token) => token,
                 Err(_) => {
@@ -188,6 +270,7 @@ pub async fn exchange_token(
                 token_type: "Bearer".to_string(),
                 expires_in: jwt_service.access_token_lifetime.num_seconds(),
                 refresh_token,
+                scope: auth_code.scope,
             })
         },
         OAuthTokenRequest::RefreshToken(req) => {
@@ -228,9 +311,13 @@ pub async fn exchange_token(
                 }
             };
             
+            let scope = refresh_data.scope
+                .clone()
+                .unwrap_or_else(|| jwt_service.grant_scopes(None));
+
             let access_token = match jwt_service.create_access_token(
                 refresh_data.user_id, 
-                "",
+                &scope,
                 roles,
             ) {
                 Ok(token) => token,
@@ -245,6 +332,7 @@ pub async fn exchange_token(
                 RefreshToken {
                     user_id: refresh_data.user_id,
                     fingerprint: req.fingerprint,
+                    scope: Some(scope.clone()),
                 }
             ).await {
                 Ok(new_token) => new_token,
@@ -259,6 +347,7 @@ pub async fn exchange_token(
                 token_type: "Bearer".to_string(),
                 expires_in: jwt_service.access_token_lifetime.num_seconds(),
                 refresh_token,
+                scope,
             })
         },
     }
@@ -267,16 +356,17 @@ pub async fn exchange_token(
 pub async fn me(
     auth: BearerAuth,
     jwt_service: web::Data<JwtService>,
+    token_store: web::Data<TokenStore>,
     user_service: web::Data<UserService>,
 ) -> impl Responder {
     let token = auth.token();
     
-    let claims = match jwt_service.validate_token(token) {
+    let claims = match validate_access_token(token, &jwt_service, &token_store).await {
         Ok(claims) => claims,
         Err(e) => {
             return HttpResponse::Unauthorized().json(ErrorResponse {
                 error: "invalid_token",
-                error_description: &e,
+                error_description: e,
             });
         }
     };
@@ -314,5 +404,16 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
             .route("/token", web::post().to(exchange_token))
             .route("/verify", web::post().to(verify_token))
             .route("/me", web::get().to(me))
+            .route("/logout", web::post().to(logout))
+            .service(
+                web::resource("/revoked/{jti}")
+                    .wrap(from_fn(require_internal_token))
+                    .route(web::get().to(revocation_status))
+            )
+            .service(
+                web::resource("/revoke")
+                    .wrap(from_fn(require_admin))
+                    .route(web::post().to(revoke))
+            )
     );
 }
\ No newline at end of file
@@ -44,6 +44,7 @@ pub struct AuthorizationRequest {
     pub redirect_uri: String,
     pub response_type: String,
     pub state: Option<String>,
+    pub scope: Option<String>,
     pub code_challenge: String,
     pub code_challenge_method: String,
 }
@@ -89,6 +90,19 @@ pub struct AuthCode {
     pub expires_at: chrono::DateTime<Utc>,
     pub code_challenge: String,
     pub code_challenge_method: String,
+    #[serde(default)]
+    pub scope: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogoutRequest {
+    /// Also invalidated when given
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeRequest {
+    pub jti: String,
 }
 
 // Output
@@ -99,12 +113,21 @@ pub struct TokenResponse {
     pub refresh_token: String,
     pub token_type: String,
     pub expires_in: i64,
+    pub scope: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RefreshToken {
     pub user_id: i32,
-    pub fingerprint: String
+    pub fingerprint: String,
+    /// Missing for tokens issued before scopes were stored
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevocationStatus {
+    pub revoked: bool,
 }
 
 #[derive(Debug, Serialize)]
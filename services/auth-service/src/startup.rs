@@ -2,13 +2,15 @@ use std::net::TcpListener;
 
 use actix_files::Files;
 use actix_session::storage::RedisSessionStore;
-use actix_web::{cookie::Key, dev::Server, http, web, App, HttpResponse, HttpServer};
+use actix_web::{cookie::Key, dev::Server, http, middleware::from_fn, web, App, HttpResponse, HttpServer};
 use actix_cors::Cors;
 use secrecy::ExposeSecret;
+use telemetry::{log_filter::LogFilterHandle, metrics::{install_recorder, metrics_handler, RequestMetrics}};
 use tracing_actix_web::TracingLogger;
 
-use crate::{auth::{client_store::ClientStore, code_store::CodeStore, jwt::JwtService, token_store::TokenStore}, config::Settings, routes::{auth, jwks, oauth}, services::user::UserService, utils::session_middleware};
+use crate::{auth::{client_store::ClientStore, code_store::CodeStore, jwt::JwtService, token_store::TokenStore}, config::Settings, routes::{auth, jwks, oauth}, services::user::UserService, utils::{require_admin, session_middleware, InternalToken}};
 
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     listener: TcpListener,
     jwt_service: JwtService,
@@ -18,12 +20,16 @@ pub fn run(
     client_store: ClientStore,
     redis_store: RedisSessionStore,
     config: Settings,
+    log_filter: LogFilterHandle,
 ) -> Result<Server, std::io::Error> {
     let jwt_service = web::Data::new(jwt_service);
     let token_store = web::Data::new(token_store);
     let code_store = web::Data::new(code_store);
     let client_store = web::Data::new(client_store);
     let user_service = web::Data::new(user_service);
+    let handle = web::Data::new(install_recorder("auth-service"));
+    let log_filter = web::Data::new(log_filter);
+    let internal_token = web::Data::new(InternalToken(config.auth.internal_token));
 
     let secret_key = if let Some(key) =  config.session.secret_key {
         Key::from(key.expose_secret().as_bytes())
@@ -34,6 +40,7 @@ pub fn run(
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
+            .wrap(RequestMetrics)
             .wrap(session_middleware(redis_store.clone(), secret_key.clone()))
             .wrap(
                 Cors::default()
@@ -50,7 +57,15 @@ pub fn run(
             .app_data(code_store.clone())
             .app_data(user_service.clone())
             .app_data(client_store.clone())
+            .app_data(handle.clone())
+            .app_data(log_filter.clone())
+            .app_data(internal_token.clone())
             .route("/health", web::to(HttpResponse::Ok))
+            .route("/metrics", web::get().to(metrics_handler))
+            .service(
+                telemetry::log_filter::scope("/admin/log-filter")
+                    .wrap(from_fn(require_admin))
+            )
             .configure(auth::configure_routes)
             .configure(oauth::configure_routes)
             .configure(jwks::configure_routes)
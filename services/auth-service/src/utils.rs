@@ -1,7 +1,10 @@
 use actix_session::{config::PersistentSession, storage::RedisSessionStore, SessionMiddleware};
-use actix_web::cookie::SameSite;
+use actix_web::{body::MessageBody, cookie::SameSite, dev::{ServiceRequest, ServiceResponse}, error::ErrorForbidden, http::header::AUTHORIZATION, middleware::Next, web, Error};
+use secrecy::{ExposeSecret, SecretBox};
 use time::Duration;
 
+use crate::auth::{jwt::{Claims, JwtService}, token_store::TokenStore};
+
 pub fn session_middleware(redis_store: RedisSessionStore, secret_key: actix_web::cookie::Key) -> SessionMiddleware<RedisSessionStore> {
     SessionMiddleware::builder(redis_store, secret_key)
         .cookie_name("auth_session".to_owned())
@@ -12,4 +15,131 @@ pub fn session_middleware(redis_store: RedisSessionStore, secret_key: actix_web:
             PersistentSession::default().session_ttl(Duration::hours(24))
         )
         .build()
+}
+
+/// Only lets through requests bearing an access token with the `admin` role.
+pub async fn require_admin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    let is_admin = match (req.app_data::<web::Data<JwtService>>(), req.app_data::<web::Data<TokenStore>>(), token) {
+        (Some(jwt_service), Some(token_store), Some(token)) => validate_access_token(token, jwt_service, token_store)
+            .await
+            .is_ok_and(|claims| claims.roles.iter().any(|role| role == "admin")),
+        _ => false,
+    };
+
+    if !is_admin {
+        return Err(ErrorForbidden("Admin access required"));
+    }
+
+    next.call(req).await
+}
+
+/// Header carrying the token of [`InternalToken`].
+pub const INTERNAL_TOKEN_HEADER: &str = "x-internal-token";
+
+/// Secret shared with the services allowed to call internal endpoints.
+pub struct InternalToken(pub Option<SecretBox<String>>);
+
+/// Only lets through requests from other services, which send the internal token.
+pub async fn require_internal_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let expected = req
+        .app_data::<web::Data<InternalToken>>()
+        .and_then(|token| token.0.as_ref())
+        .map(|token| token.expose_secret().as_bytes())
+        .filter(|token| !token.is_empty());
+
+    let token = req
+        .headers()
+        .get(INTERNAL_TOKEN_HEADER)
+        .map(|h| h.as_bytes());
+
+    let is_internal = match (expected, token) {
+        (Some(expected), Some(token)) => constant_time_eq(expected, token),
+        _ => false,
+    };
+
+    if !is_internal {
+        return Err(ErrorForbidden("Internal access required"));
+    }
+
+    next.call(req).await
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Validates an access token, rejecting it once revoked.
+pub async fn validate_access_token(
+    token: &str,
+    jwt_service: &JwtService,
+    token_store: &TokenStore,
+) -> Result<Claims, &'static str> {
+    let claims = jwt_service.validate_token(token)?;
+
+    match token_store.is_access_token_revoked(&claims.jti).await {
+        Ok(false) => Ok(claims),
+        Ok(true) => Err("Token revoked"),
+        Err(e) => {
+            tracing::error!("Failed to check token revocation: {:?}", e);
+            Err("Failed to validate token")
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, middleware::from_fn, test, web, App, HttpResponse};
+    use secrecy::SecretBox;
+
+    use super::{require_internal_token, InternalToken, INTERNAL_TOKEN_HEADER};
+
+    async fn status(configured: Option<&str>, sent: Option<&str>) -> StatusCode {
+        let token = configured.map(|token| SecretBox::new(Box::new(token.to_string())));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(InternalToken(token)))
+                .wrap(from_fn(require_internal_token))
+                .route("/", web::get().to(HttpResponse::Ok))
+        ).await;
+
+        let mut req = test::TestRequest::get();
+        if let Some(sent) = sent {
+            req = req.insert_header((INTERNAL_TOKEN_HEADER, sent));
+        }
+
+        match test::try_call_service(&app, req.to_request()).await {
+            Ok(res) => res.status(),
+            Err(e) => e.as_response_error().status_code(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn lets_through_the_configured_token() {
+        assert_eq!(status(Some("secret"), Some("secret")).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn rejects_missing_and_wrong_tokens() {
+        assert_eq!(status(Some("secret"), None).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Some("secret"), Some("secre")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Some("secret"), Some("public")).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn rejects_everything_while_unset() {
+        assert_eq!(status(None, Some("")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Some(""), Some("")).await, StatusCode::FORBIDDEN);
+    }
 }
\ No newline at end of file
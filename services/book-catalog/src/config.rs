@@ -2,6 +2,7 @@ use serde::Deserialize;
 use secrecy::{ExposeSecret, SecretBox};
 use sea_orm::ConnectOptions;
 use serde_aux::field_attributes::deserialize_number_from_string;
+use telemetry::{otel::OtlpSettings, output::LogSettings};
 
 #[derive(Deserialize, Debug)]
 pub struct Settings {
@@ -9,7 +10,11 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub search: SearchSettings,
     pub cache: CacheSettings,
-    pub s3: S3Settings
+    pub s3: S3Settings,
+    pub auth: AuthSettings,
+    pub otlp: Option<OtlpSettings>,
+    #[serde(default)]
+    pub log: LogSettings
 }
 
 #[derive(Deserialize, Debug)]
@@ -37,7 +42,16 @@ pub struct DatabaseSettings {
 
 #[derive(Deserialize, Debug)]
 pub struct CacheSettings {
-    pub url: String
+    pub url: String,
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub warm_up_books: u64
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuthSettings {
+    pub url: String,
+    /// Lets the service check token revocations with auth-service
+    pub internal_token: Option<SecretBox<String>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -1,19 +1,25 @@
 use std::{net::TcpListener, time::Duration};
 
-use bb8_redis::{RedisConnectionManager, bb8::Pool};
+use auth_client::jwt::JwtValidator;
+use bb8_redis::{RedisConnectionManager, bb8::Pool, redis::Client};
 use book_catalog::{
     config::get_config, migration::Migrator, search::ElasticsearchClient, startup::run, storage::s3::S3StorageBackend
 };
 use sea_orm::Database;
+use secrecy::ExposeSecret;
 use sea_orm_migration::MigratorTrait;
-use telemetry::{get_subscriber, init_subscriber};
+use telemetry::{get_subscriber, init_subscriber, otel::{init_tracer_provider, shutdown_tracer_provider}};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let subscriber = get_subscriber("book-catalog".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-
     let config = get_config().expect("Failed to read config");
+
+    let tracer_provider = config.otlp
+        .as_ref()
+        .map(|otlp| init_tracer_provider(env!("CARGO_PKG_NAME"), otlp).expect("Failed to build OTLP exporter"));
+
+    let (subscriber, log_filter) = get_subscriber("book-catalog".into(), "info".into(), std::io::stdout, &config.log, tracer_provider.as_ref());
+    init_subscriber(subscriber);
     
     let db = Database::connect(config.database.get_options()).await.unwrap();
 
@@ -36,8 +42,17 @@ async fn main() -> std::io::Result<()> {
             .build(redis_manager)
             .await
             .expect("Failed to build Redis pool");
+    let redis_client = Client::open(config.cache.url.clone())
+        .expect("Failed to create Redis client");
 
     let storage = S3StorageBackend::new(config.s3);
 
-    run(listener, db, search, redis_pool, storage)?.await
+    let jwt_validator = JwtValidator::new(config.auth.url)
+        .internal_token(config.auth.internal_token.map(|token| token.expose_secret().clone()));
+
+    let result = run(listener, db, search, redis_pool, redis_client, storage, config.cache.warm_up_books, log_filter, jwt_validator)?.await;
+
+    shutdown_tracer_provider(tracer_provider);
+
+    result
 }
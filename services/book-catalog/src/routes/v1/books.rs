@@ -1,8 +1,8 @@
-use std::io::Read;
+use std::{io::Read, time::Duration};
 
 use actix_multipart::form::MultipartForm;
 use actix_web::{web, HttpResponse, Responder};
-use cache::{cache::HybridCache, expiry::Expiration, serializer::bitcode::BitcodeSerializer};
+use cache::{cache::HybridCache, expiry::Expiration, serializer::{bitcode::BitcodeSerializer, compressed::CompressedSerializer}};
 use sea_orm::{prelude::Expr, ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DerivePartialModel, EntityTrait, FromQueryResult, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, RelationTrait, TransactionTrait};
 use uuid::Uuid;
 use serde_qs::actix::QsQuery;
@@ -15,6 +15,12 @@ use crate::{
 
 const DEFAULT_PAGE_SIZE: u64 = 50;
 
+// Warmed-up books are written together, jitter keeps them from expiring together
+const BOOK_EXPIRY: Expiration = Expiration::Jittered {
+    ttl: Duration::from_secs(10 * 60),
+    jitter: Duration::from_secs(60),
+};
+
 #[derive(FromQueryResult, DerivePartialModel)]
 #[sea_orm(entity = "book::Entity")]
 struct BookCover {
@@ -24,7 +30,7 @@ struct BookCover {
 pub async fn get_books(db: web::Data<DatabaseConnection>, query: QsQuery<GetListSchema>) -> impl Responder {
     let query = query.into_inner();
     let page_size = query.page_size
-        .and_then(|size| Some(size.clamp(10, 100)))
+        .map(|size| size.clamp(10, 100))
         .unwrap_or(DEFAULT_PAGE_SIZE);
 
     let order_by = query.order_by.unwrap_or(crate::schema::OrderBy::CreatedAt);
@@ -172,21 +178,72 @@ pub async fn get_books(db: web::Data<DatabaseConnection>, query: QsQuery<GetList
     HttpResponse::Ok().json(resp)
 }
 
+#[derive(Debug)]
+enum GetBookError {
+    NotFound,
+    Internal,
+}
+
 pub async fn get_book(
     db: web::Data<DatabaseConnection>,
-    cache: web::Data<HybridCache<String, BookFullSchema, BitcodeSerializer<BookFullSchema>>>,
+    cache: web::Data<HybridCache<String, BookFullSchema, CompressedSerializer<BitcodeSerializer<BookFullSchema>>>>,
     query: web::Path<GetBookSchema>,
 ) -> impl Responder {
-    match cache.get(
+    let result = cache.get_or_load(
         &query.id.to_string(),
-        Expiration::Minutes(10)
-    ).await {
-        Ok(Some(book)) => return HttpResponse::Ok().json(book),
-        Ok(None) => (),
-        Err(e) => tracing::error!("Failed to get book from cache: {:?}", e),
+        BOOK_EXPIRY,
+        || fetch_book(db.as_ref(), query.id)
+    ).await;
+
+    match result {
+        Ok(book) => HttpResponse::Ok().json(book),
+        Err(e) => match *e {
+            GetBookError::NotFound => HttpResponse::NotFound().body("Book not found!"),
+            GetBookError::Internal => HttpResponse::InternalServerError().finish(),
+        },
+    }
+}
+
+/// Preloads the `count` most recently created books.
+pub async fn warm_up_books(
+    db: web::Data<DatabaseConnection>,
+    cache: web::Data<HybridCache<String, BookFullSchema, CompressedSerializer<BitcodeSerializer<BookFullSchema>>>>,
+    count: u64
+) {
+    let result = Book::find()
+        .select_only()
+        .column(book::Column::Id)
+        .order_by_desc(book::Column::CreatedAt)
+        .limit(count)
+        .into_tuple::<i32>()
+        .all(db.as_ref())
+        .await;
+
+    let ids = match result {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::error!("Failed to select books for cache warm-up: {:?}", e);
+            return
+        },
     };
 
-    let result = Book::find_by_id(query.id)
+    let keys = ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect();
+
+    cache.warm_up(keys, BOOK_EXPIRY, move |key| {
+        let db = db.clone();
+
+        async move {
+            let id = key.parse().map_err(|_| GetBookError::NotFound)?;
+            fetch_book(db.as_ref(), id).await
+        }
+    });
+}
+
+async fn fetch_book(db: &DatabaseConnection, id: i32) -> Result<BookFullSchema, GetBookError> {
+    let result = Book::find_by_id(id)
         .select_only()
         .columns([
             book::Column::Id,
@@ -248,37 +305,22 @@ pub async fn get_book(
         .join(sea_orm::JoinType::LeftJoin, book_author::Relation::Author.def())
         .group_by(book::Column::Id)
         .into_json()
-        .all(db.as_ref())
+        .all(db)
         .await;
     
     match result {
         Ok(mut books) if !books.is_empty()=> {
             let book_value = books[0].take();
-            let result = serde_json::from_value::<BookFullSchema>(book_value);
-
-            match result {
-                Ok(book) => {
-                    if let Err(e) = cache.set(
-                        query.id.to_string(),
-                        book.clone(),
-                        Expiration::Minutes(10)
-                    ).await {
-                        tracing::error!("Failed to insert book into cache: {:?}", e);
-                    };
-                    HttpResponse::Ok().json(book)
-                },
-                Err(e) => {
-                    tracing::error!("Deserialization failed: {:?}", e);
-                    HttpResponse::InternalServerError().finish()
-                },
-            }
+
+            serde_json::from_value::<BookFullSchema>(book_value).map_err(|e| {
+                tracing::error!("Deserialization failed: {:?}", e);
+                GetBookError::Internal
+            })
         },
-        Ok(_) => {
-            HttpResponse::NotFound().body("Book not found!")
-        }
+        Ok(_) => Err(GetBookError::NotFound),
         Err(e) => {
             tracing::error!("Failed to select book: {:?}", e);
-            HttpResponse::InternalServerError().finish()
+            Err(GetBookError::Internal)
         },
     }
 }
@@ -410,7 +452,7 @@ pub async fn update_book(
     db: web::Data<DatabaseConnection>,
     storage: web::Data<S3StorageBackend>,
     book_id: web::Path<i32>,
-    cache: web::Data<HybridCache<String, BookFullSchema, BitcodeSerializer<BookFullSchema>>>,
+    cache: web::Data<HybridCache<String, BookFullSchema, CompressedSerializer<BitcodeSerializer<BookFullSchema>>>>,
     MultipartForm(form): MultipartForm<UpdateBookForm>
 ) -> impl Responder {
     let cover = form.cover;
@@ -1,6 +1,6 @@
 use actix_web::{web, HttpResponse, Responder};
 use cache::{cache::HybridCache, expiry::Expiration, serializer::bitcode::BitcodeSerializer};
-use sea_orm::{DatabaseConnection, EntityTrait, Iterable};
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, Iterable};
 
 use crate::{
     entity::{
@@ -8,47 +8,52 @@ use crate::{
     }, schema::{ConstantsSchema, Genre, Tag}
 };
 
+const CONSTANTS_EXPIRY: Expiration = Expiration::Minutes(10);
+
 pub async fn get_constants(
     db: web::Data<DatabaseConnection>,
     cache: web::Data<HybridCache<String, ConstantsSchema, BitcodeSerializer<ConstantsSchema>>>
 ) -> impl Responder {
-    match cache.get(&String::new(), Expiration::Minutes(10)).await {
-        Ok(Some(consts)) => return HttpResponse::Ok().json(consts),
-        Ok(None) => (),
-        Err(e) => tracing::error!("Failed to get constants from cache: {:?}", e),
-    };
+    let result = cache.get_or_load(
+        &String::new(),
+        CONSTANTS_EXPIRY,
+        || fetch_constants(db.as_ref())
+    ).await;
+
+    match result {
+        Ok(consts) => HttpResponse::Ok().json(consts),
+        Err(e) => {
+            tracing::error!("Failed to fetch constants from db: {:?}", e);
+            HttpResponse::InternalServerError().finish()
+        },
+    }
+}
+
+pub fn warm_up_constants(
+    db: web::Data<DatabaseConnection>,
+    cache: web::Data<HybridCache<String, ConstantsSchema, BitcodeSerializer<ConstantsSchema>>>
+) {
+    cache.warm_up(vec![String::new()], CONSTANTS_EXPIRY, move |_| {
+        let db = db.clone();
 
-    let tags = match tag::Entity::find()
+        async move { fetch_constants(db.as_ref()).await }
+    });
+}
+
+async fn fetch_constants(db: &DatabaseConnection) -> Result<ConstantsSchema, DbErr> {
+    let tags = tag::Entity::find()
         .into_partial_model::<Tag>()
-        .all(db.as_ref())
-        .await {
-            Ok(data) => data,
-            Err(e) => {
-                tracing::error!("Failed to fetch tags from db: {:?}", e);
-                return HttpResponse::InternalServerError().finish()
-            },
-        };
-
-    let genres = match genre::Entity::find()
+        .all(db)
+        .await?;
+
+    let genres = genre::Entity::find()
         .into_partial_model::<Genre>()
-        .all(db.as_ref())
-        .await {
-            Ok(data) => data,
-            Err(e) => {
-                tracing::error!("Failed to fetch genres from db: {:?}", e);
-                return HttpResponse::InternalServerError().finish()
-            },
-        };
-
-    let constants = ConstantsSchema {
+        .all(db)
+        .await?;
+
+    Ok(ConstantsSchema {
         tags,
         genres,
         status: BookStatus::iter().collect()
-    };
-
-    if let Err(e) = cache.set(String::new(), constants.clone(), Expiration::Minutes(10)).await {
-        tracing::error!("Failed to insert constants into cache: {:?}", e);
-    };
-
-    HttpResponse::Ok().json(constants)
+    })
 }
\ No newline at end of file
@@ -1,10 +1,15 @@
-use actix_web::web;
+use actix_web::{middleware::from_fn, web::{self, Data}};
+use auth_client::guard::require_admin;
+use cache::{actix::admin, cache::HybridCache, serializer::{bitcode::BitcodeSerializer, compressed::CompressedSerializer}};
 use authors::{create_author, delete_author, get_author, update_author};
-use books::{create_book, get_book, get_books, update_book, delete_book};
+use books::{create_book, get_book, get_books, update_book, delete_book, warm_up_books};
 use chapters::{create_chapter, delete_chapter, get_chapter, get_chapters, update_chapter};
-use constants::get_constants;
+use constants::{get_constants, warm_up_constants};
+use sea_orm::DatabaseConnection;
 use search::{search_authors, search_books};
 
+use crate::schema::{BookFullSchema, ConstantsSchema};
+
 mod constants;
 mod search;
 mod books;
@@ -40,5 +45,27 @@ pub fn config(cfg: &mut web::ServiceConfig) {
                     .route("/authors", web::get().to(search_authors))
             )
             .route("/constants", web::get().to(get_constants))
+            // Exposed to admins through the gateway, which forwards their token
+            .service(
+                web::scope("/cache")
+                    .wrap(from_fn(require_admin))
+                    .service(admin::scope::<ConstantsSchema, BitcodeSerializer<ConstantsSchema>>("/constants"))
+                    .service(admin::scope::<BookFullSchema, CompressedSerializer<BitcodeSerializer<BookFullSchema>>>("/book-full"))
+            )
     );
+}
+
+/// Fills the constants and book caches in the background so the first requests
+/// after a deploy don't all fall through to Redis and Postgres.
+pub fn warm_up_caches(
+    db: Data<DatabaseConnection>,
+    constants_cache: Data<HybridCache<String, ConstantsSchema, BitcodeSerializer<ConstantsSchema>>>,
+    book_full_cache: Data<HybridCache<String, BookFullSchema, CompressedSerializer<BitcodeSerializer<BookFullSchema>>>>,
+    books: u64
+) {
+    warm_up_constants(db.clone(), constants_cache);
+
+    if books > 0 {
+        actix_web::rt::spawn(warm_up_books(db, book_full_cache, books));
+    }
 }
\ No newline at end of file
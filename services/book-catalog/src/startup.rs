@@ -1,56 +1,64 @@
-use std::net::TcpListener;
+use std::{net::TcpListener, time::Duration};
 
-use actix_web::{dev::Server, web::{self, Data}, App, HttpResponse, HttpServer};
-use bb8_redis::{bb8::Pool, RedisConnectionManager};
-use cache::{cache::HybridCache, serializer::bitcode::BitcodeSerializer};
-use metrics_exporter_prometheus::PrometheusBuilder;
+use actix_web::{dev::Server, middleware::from_fn, web::{self, Data}, App, HttpResponse, HttpServer};
+use auth_client::{guard::require_admin, jwt::JwtValidator};
+use bb8_redis::{bb8::Pool, redis::Client, RedisConnectionManager};
+use cache::{cache::HybridCache, serializer::{bitcode::BitcodeSerializer, compressed::{CompressedSerializer, Compression}}};
 use sea_orm::DatabaseConnection;
+use telemetry::{log_filter::LogFilterHandle, metrics::{install_recorder, metrics_handler, RequestMetrics}};
 use tracing_actix_web::TracingLogger;
 
-use crate::{routes::{metrics::get_metrics, v1}, schema::{BookFullSchema, ConstantsSchema}, search::elasticsearch::ElasticsearchClient, storage::s3::S3StorageBackend};
+use crate::{routes::v1, schema::{BookFullSchema, ConstantsSchema}, search::elasticsearch::ElasticsearchClient, storage::s3::S3StorageBackend};
 
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     listener: TcpListener,
     db: DatabaseConnection,
     search: ElasticsearchClient,
     redis_pool: Pool<RedisConnectionManager>,
-    storage: S3StorageBackend
+    redis_client: Client,
+    storage: S3StorageBackend,
+    warm_up_books: u64,
+    log_filter: LogFilterHandle,
+    jwt_validator: JwtValidator
 ) -> Result<Server, std::io::Error> {
     let db = Data::new(db);
     let search = Data::new(search);
     let storage = Data::new(storage);
+    let log_filter = Data::new(log_filter);
+    let validator = Data::new(jwt_validator);
 
     let constants_cache = Data::new(HybridCache::<String, ConstantsSchema, BitcodeSerializer<_>>::new(
         "constants".to_string(),
         redis_pool.clone(),
         1,
         BitcodeSerializer::default()
-    ));
+    ).with_invalidation_channel(redis_client.clone()));
     
-    let book_full_cache = Data::new(HybridCache::<String, BookFullSchema, BitcodeSerializer<_>>::new(
+    let book_full_cache = Data::new(HybridCache::<String, BookFullSchema, CompressedSerializer<BitcodeSerializer<_>>>::new(
         "book-full".to_string(),
         redis_pool,
         50000,
-        BitcodeSerializer::default()
-    ));
-
-    let builder = PrometheusBuilder::new();
+        CompressedSerializer::new(BitcodeSerializer::default(), Compression::Zstd(3))
+    )
+    .with_version(1)
+    .with_invalidation_channel(redis_client)
+    .with_load_lock(Duration::from_secs(2)));
 
-    let handle = builder
-        .add_global_label("service", "book-catalog")
-        .install_recorder()
-        .expect("Failed to install metrics recorder");
+    v1::warm_up_caches(db.clone(), constants_cache.clone(), book_full_cache.clone(), warm_up_books);
 
-    let handle = Data::new(handle);
+    let handle = Data::new(install_recorder("book-catalog"));
 
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
+            .wrap(RequestMetrics)
             .app_data(db.clone())
             .app_data(search.clone())
             .app_data(constants_cache.clone())
             .app_data(book_full_cache.clone())
             .app_data(storage.clone())
+            .app_data(validator.clone())
             .route("/health", web::to(HttpResponse::Ok))
             .service(
                 web::scope("/api")
@@ -58,7 +66,9 @@ pub fn run(
             )
             // Metrics
             .app_data(handle.clone())
-            .route("/metrics", web::get().to(get_metrics))
+            .route("/metrics", web::get().to(metrics_handler))
+            .app_data(log_filter.clone())
+            .service(telemetry::log_filter::scope("/admin/log-filter").wrap(from_fn(require_admin)))
     })
     .listen(listener)?
     .run();
@@ -127,7 +127,7 @@ impl S3StorageBackend {
     }
 
     pub fn extract_uuid_from_url(&self, url: &str) -> Option<Uuid> {
-        if let Some(last_part) = url.split('/').last() {
+        if let Some(last_part) = url.split('/').next_back() {
             if let Some(uuid_str) = last_part.split('.').next() {
                 if let Ok(uuid) = Uuid::parse_str(uuid_str) {
                     return Some(uuid);
@@ -7,7 +7,7 @@ export const AUTH_CONFIG = {
     tokenEndpoint: 'http://127.0.0.1:5001/oauth/token',
     userInfoEndpoint: 'http://127.0.0.1:5001/oauth/me',
     redirectUri: window.location.origin + '/callback',
-    scope: ''
+    scope: 'ratings:write'
 };
 
 const fpPromise = FingerprintJS.load();
@@ -2,12 +2,17 @@ use secrecy::{SecretBox, ExposeSecret};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::{postgres::{PgConnectOptions, PgSslMode}, ConnectOptions};
+use telemetry::{otel::OtlpSettings, output::LogSettings};
 
 #[derive(Deserialize, Debug)]
 pub struct Settings {
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub redis: RedisSettings,
+    pub auth: AuthSettings,
+    pub otlp: Option<OtlpSettings>,
+    #[serde(default)]
+    pub log: LogSettings,
 }
 
 #[derive(Deserialize, Debug)]
@@ -22,6 +27,13 @@ pub struct RedisSettings {
     pub url: String
 }
 
+#[derive(Deserialize, Debug)]
+pub struct AuthSettings {
+    pub url: String,
+    /// Lets the service check token revocations with auth-service
+    pub internal_token: Option<SecretBox<String>>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct DatabaseSettings {
     pub username: String,
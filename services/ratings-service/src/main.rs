@@ -1,18 +1,24 @@
-use bb8_redis::{bb8::Pool, RedisConnectionManager};
+use auth_client::jwt::JwtValidator;
+use bb8_redis::{bb8::Pool, redis::Client, RedisConnectionManager};
 use ratings_service::config::get_config;
+use secrecy::ExposeSecret;
 use sqlx::postgres::PgPoolOptions;
-use telemetry::{get_subscriber, init_subscriber};
+use telemetry::{get_subscriber, init_subscriber, otel::{init_tracer_provider, shutdown_tracer_provider}};
 use std::{net::TcpListener, time::Duration};
 
 use ratings_service::startup::run;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let subscriber = get_subscriber("ratings-service".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-
     let config = get_config().unwrap();
 
+    let tracer_provider = config.otlp
+        .as_ref()
+        .map(|otlp| init_tracer_provider(env!("CARGO_PKG_NAME"), otlp).expect("Failed to build OTLP exporter"));
+
+    let (subscriber, log_filter) = get_subscriber("ratings-service".into(), "info".into(), std::io::stdout, &config.log, tracer_provider.as_ref());
+    init_subscriber(subscriber);
+
     let connection_pool = PgPoolOptions::new()
         .connect_lazy_with(config.database.with_db());
 
@@ -34,5 +40,15 @@ async fn main() -> std::io::Result<()> {
             .await
             .expect("Failed to build Redis pool");
 
-    run(listener, connection_pool, redis_pool)?.await
+    let redis_client = Client::open(config.redis.url.clone())
+        .expect("Failed to create Redis client");
+
+    let jwt_validator = JwtValidator::new(config.auth.url)
+        .internal_token(config.auth.internal_token.map(|token| token.expose_secret().clone()));
+
+    let result = run(listener, connection_pool, redis_pool, redis_client, log_filter, jwt_validator)?.await;
+
+    shutdown_tracer_provider(tracer_provider);
+
+    result
 }
\ No newline at end of file
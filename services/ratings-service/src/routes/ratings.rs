@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use actix_web::{web, HttpResponse, Responder};
 use cache::{cache::HybridCache, expiry::Expiration, serializer::bitcode::BitcodeSerializer};
 use sqlx::PgPool;
@@ -11,65 +13,67 @@ pub async fn get_rating(
     cache: web::Data<HybridCache::<String, RatingSchema, BitcodeSerializer<RatingSchema>>>
 ) -> impl Responder {
     let path = path.into_inner();
-    let key = format!("{}_{}", path, json.user_id.unwrap_or(-1));
-    match cache.get(
-        &key,
-        Expiration::Minutes(10)
-    ).await {
-        Ok(Some(rating)) => return HttpResponse::Ok().json(rating),
-        Ok(None) => (),
-        Err(e) => tracing::error!("Failed to get rating from cache: {:?}", e),
-    };
 
-    let rating = if let Some(user_id) = json.user_id {
-        sqlx::query_as!(
-            RatingSchema,
-            r#"
-            SELECT avg_rating::REAL as "avg!",
-                (SELECT rating FROM ratings WHERE book_id = $1 AND user_id = $2) as "user!"
-            FROM book_rating_stats
-            WHERE book_id = $1
-            "#,
-            path,
-            user_id
-        )
-        .fetch_one(pool.get_ref())
-        .await
-    } else {
-        sqlx::query_as!(
-            RatingSchema,
-            r#"
-            SELECT avg_rating::REAL as "avg!", NULL::SMALLINT as "user"
-            FROM book_rating_stats
-            WHERE book_id = $1
-            "#,
-            path
-        )
-        .fetch_one(pool.get_ref())
-        .await
-    };
+    // Only the average is cached, the caller's own rating is looked up on every request
+    let result = cache.get_or_load(
+        &format!("{}_-1", path),
+        Expiration::Minutes(10),
+        || fetch_rating(pool.get_ref(), path)
+    ).await;
 
-    match rating {
-        Ok(rating) => {
-            if let Err(e) = cache.set(
-                key,
-                rating.clone(),
-                Expiration::Minutes(10)
-            ).await {
-                tracing::error!("Failed to insert book into cache: {:?}", e)
-            }
-            HttpResponse::Ok().json(rating)
-        },
+    let mut rating = match result {
+        Ok(rating) => rating,
         Err(e) => {
             tracing::error!("Failed to get rating: {:?}", e);
-            HttpResponse::InternalServerError().finish()
+            return HttpResponse::InternalServerError().finish();
         },
+    };
+
+    if let Some(user_id) = json.user_id {
+        match fetch_user_rating(pool.get_ref(), path, user_id).await {
+            Ok(user) => rating.user = user,
+            Err(e) => {
+                tracing::error!("Failed to get user rating: {:?}", e);
+                return HttpResponse::InternalServerError().finish();
+            },
+        }
     }
+
+    HttpResponse::Ok().json(rating)
+}
+
+async fn fetch_rating(pool: &PgPool, book_id: i32) -> Result<RatingSchema, sqlx::Error> {
+    sqlx::query_as!(
+        RatingSchema,
+        r#"
+        SELECT avg_rating::REAL as "avg!", NULL::SMALLINT as "user"
+        FROM book_rating_stats
+        WHERE book_id = $1
+        "#,
+        book_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn fetch_user_rating(pool: &PgPool, book_id: i32, user_id: i32) -> Result<Option<i16>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT rating
+        FROM ratings
+        WHERE book_id = $1 AND user_id = $2
+        "#,
+        book_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
 }
 
 pub async fn bulk_get(
     pool: web::Data<PgPool>,
-    schema: web::Json<BulkGetSchema>
+    schema: web::Json<BulkGetSchema>,
+    cache: web::Data<HybridCache::<String, RatingSchema, BitcodeSerializer<RatingSchema>>>
 ) -> impl Responder {
     let ids = schema.into_inner().ids;
 
@@ -77,20 +81,62 @@ pub async fn bulk_get(
         return HttpResponse::BadGateway().finish();
     }
 
-    let ratings = sqlx::query_as!(
+    // Shares entries with `get_rating` for anonymous users
+    let keys = ids.iter()
+        .map(|id| format!("{}_-1", id))
+        .collect::<Vec<_>>();
+
+    let mut cached = match cache.get_many(&keys, Expiration::Minutes(10)).await {
+        Ok(result) => result.hits,
+        Err(e) => {
+            tracing::error!("Failed to get ratings from cache: {:?}", e);
+            HashMap::new()
+        }
+    };
+
+    let mut ratings = Vec::with_capacity(ids.len());
+    let mut missing = Vec::new();
+
+    for (id, key) in ids.into_iter().zip(&keys) {
+        match cached.remove(key) {
+            Some(rating) => ratings.push(BookRatingSchema { book_id: id, avg_rating: rating.avg }),
+            None => missing.push(id),
+        }
+    }
+
+    if missing.is_empty() {
+        return HttpResponse::Ok().json(ratings);
+    }
+
+    let fetched = sqlx::query_as!(
         BookRatingSchema,
         r#"
         SELECT book_id, avg_rating::REAL as "avg_rating!"
         FROM book_rating_stats
         WHERE book_id = ANY($1)
         "#,
-        &ids
+        &missing
     )
     .fetch_all(pool.get_ref())
     .await;
 
-    match ratings {
-        Ok(ratings) => HttpResponse::Ok().json(ratings),
+    match fetched {
+        Ok(fetched) => {
+            let entries = fetched.iter()
+                .map(|rating| (
+                    format!("{}_-1", rating.book_id),
+                    RatingSchema { avg: rating.avg_rating, user: None }
+                ))
+                .collect();
+
+            if let Err(e) = cache.set_many(entries, Expiration::Minutes(10)).await {
+                tracing::error!("Failed to insert ratings in cache: {:?}", e);
+            }
+
+            ratings.extend(fetched);
+
+            HttpResponse::Ok().json(ratings)
+        },
         Err(e) => {
             tracing::error!("Failed to bulk get ratings: {}", e);
             HttpResponse::InternalServerError().finish()
@@ -100,7 +146,8 @@ pub async fn bulk_get(
 
 pub async fn rate(
     pool: web::Data<PgPool>,
-    schema: web::Json<RateSchema>
+    schema: web::Json<RateSchema>,
+    cache: web::Data<HybridCache::<String, RatingSchema, BitcodeSerializer<RatingSchema>>>
 ) -> impl Responder {
     let schema = schema.into_inner();
 
@@ -118,7 +165,10 @@ pub async fn rate(
             .await;
 
             match result {
-                Ok(_) => HttpResponse::Ok().finish(),
+                Ok(_) => {
+                    invalidate_rating(&cache, schema.item_id).await;
+                    HttpResponse::Ok().finish()
+                },
                 Err(e) => {
                     tracing::error!("Failed to delete rating: {:?}", e);
                     HttpResponse::InternalServerError().finish()
@@ -141,7 +191,10 @@ pub async fn rate(
             .await;
 
             match result {
-                Ok(_) => HttpResponse::Created().finish(),
+                Ok(_) => {
+                    invalidate_rating(&cache, schema.item_id).await;
+                    HttpResponse::Created().finish()
+                },
                 Err(e) => {
                     tracing::error!("Failed to insert rating: {:?}", e);
                     HttpResponse::InternalServerError().finish()
@@ -152,4 +205,14 @@ pub async fn rate(
             HttpResponse::BadRequest().finish()
         }
     }
+}
+
+/// Drops the cached average used by `get_rating` and `bulk_get`.
+async fn invalidate_rating(
+    cache: &HybridCache::<String, RatingSchema, BitcodeSerializer<RatingSchema>>,
+    book_id: i32,
+) {
+    if let Err(e) = cache.invalidate(format!("{}_-1", book_id)).await {
+        tracing::error!("Failed to invalidate cached rating: {:?}", e);
+    }
 }
\ No newline at end of file
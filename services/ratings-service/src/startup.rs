@@ -1,9 +1,11 @@
 use std::net::TcpListener;
 
-use actix_web::{dev::Server, web::{self, Data}, App, HttpResponse, HttpServer};
-use bb8_redis::{bb8::Pool, RedisConnectionManager};
+use actix_web::{dev::Server, middleware::from_fn, web::{self, Data}, App, HttpResponse, HttpServer};
+use auth_client::{guard::require_admin, jwt::JwtValidator};
+use bb8_redis::{bb8::Pool, redis::Client, RedisConnectionManager};
 use cache::{cache::HybridCache, serializer::bitcode::BitcodeSerializer};
 use sqlx::PgPool;
+use telemetry::{log_filter::LogFilterHandle, metrics::{install_recorder, metrics_handler, RequestMetrics}};
 use tracing_actix_web::TracingLogger;
 
 use crate::{routes::configure_routes, schema::RatingSchema};
@@ -12,22 +14,35 @@ pub fn run(
     listener: TcpListener,
     pool: PgPool,
     redis_pool: Pool<RedisConnectionManager>,
+    redis_client: Client,
+    log_filter: LogFilterHandle,
+    jwt_validator: JwtValidator,
 ) -> Result<Server, std::io::Error> {
     let pool = web::Data::new(pool);
+    let log_filter = web::Data::new(log_filter);
+    let validator = web::Data::new(jwt_validator);
 
     let cache = Data::new(HybridCache::<String, RatingSchema, BitcodeSerializer<_>>::new(
         "ratings".to_string(),
         redis_pool,
         10000,
         BitcodeSerializer::default()
-    ));
+    ).with_invalidation_channel(redis_client));
+
+    let handle = Data::new(install_recorder("ratings-service"));
 
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
+            .wrap(RequestMetrics)
             .app_data(pool.clone())
             .app_data(cache.clone())
+            .app_data(handle.clone())
+            .app_data(log_filter.clone())
+            .app_data(validator.clone())
             .route("/health", web::to(HttpResponse::Ok))
+            .route("/metrics", web::get().to(metrics_handler))
+            .service(telemetry::log_filter::scope("/admin/log-filter").wrap(from_fn(require_admin)))
             .configure(configure_routes)
     })
     .listen(listener)?